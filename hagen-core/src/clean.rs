@@ -0,0 +1,287 @@
+use failure::Error;
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use log::{debug, info};
+use walkdir::WalkDir;
+
+use crate::error::GeneratorError;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// The suffix of the marker file, which identifies an output directory created by hagen.
+///
+/// The marker file is stored next to the output directory, so that it doesn't get deployed with
+/// the site. For an output directory `output` it is named `.output.hagen`, and contains the list
+/// of files produced by the last build.
+pub const MARKER: &str = ".hagen";
+
+/// Get the path of the marker file of an output directory.
+pub fn marker_path<P: AsRef<Path>>(output: P) -> Result<PathBuf> {
+    let output = output.as_ref();
+    let name = output.file_name().ok_or_else(|| {
+        GeneratorError::Error(format!("Output directory must have a name: {:?}", output))
+    })?;
+
+    Ok(output.with_file_name(format!(".{}{}", name.to_string_lossy(), MARKER)))
+}
+
+/// Remove the output directory, and re-create it.
+///
+/// Refuses to remove an existing, non-empty directory which doesn't have a marker file.
+pub fn clean_all<P: AsRef<Path>>(output: P) -> Result<()> {
+    let output = output.as_ref();
+
+    let mut files = BTreeSet::new();
+    if output.exists() {
+        check_marker(output)?;
+        files = read_marker(output)?;
+        info!("Cleaning up: {:?}", output);
+        fs::remove_dir_all(output)?;
+    }
+
+    fs::create_dir_all(output)?;
+    write_marker(output, &files)?;
+
+    Ok(())
+}
+
+/// Take over an output directory built by an earlier version, which didn't write a marker file.
+///
+/// Earlier versions always removed the whole directory, so all of its files count as produced.
+pub fn adopt<P: AsRef<Path>>(output: P) -> Result<()> {
+    let output = output.as_ref();
+
+    if output.is_dir() && !marker_path(output)?.is_file() {
+        info!("Taking over output directory: {:?}", output);
+        write_marker(output, &snapshot(output)?)?;
+    }
+
+    Ok(())
+}
+
+/// Only remove the files which were produced by the previous build.
+pub fn clean_previous<P: AsRef<Path>>(output: P) -> Result<()> {
+    let output = output.as_ref();
+
+    let mut files = BTreeSet::new();
+    if output.exists() {
+        check_marker(output)?;
+
+        files = read_marker(output)?;
+        info!(
+            "Cleaning up {} files from previous build: {:?}",
+            files.len(),
+            output
+        );
+
+        for file in &files {
+            let path = output.join(file);
+            if path.is_file() {
+                debug!("Remove: {:?}", path);
+                fs::remove_file(&path)?;
+                remove_empty_parents(output, &path)?;
+            }
+        }
+    }
+
+    fs::create_dir_all(output)?;
+    write_marker(output, &files)?;
+
+    Ok(())
+}
+
+/// Get all files currently present in the output directory, relative to it.
+pub fn snapshot<P: AsRef<Path>>(output: P) -> Result<BTreeSet<PathBuf>> {
+    let output = output.as_ref();
+    let mut result = BTreeSet::new();
+
+    for entry in WalkDir::new(output).into_iter().filter_map(|e| e.ok()) {
+        if entry.file_type().is_file() {
+            result.insert(entry.path().strip_prefix(output)?.to_path_buf());
+        }
+    }
+
+    Ok(result)
+}
+
+/// Write the marker file, recording the list of produced files.
+pub fn write_marker<P: AsRef<Path>>(output: P, files: &BTreeSet<PathBuf>) -> Result<()> {
+    let mut writer = File::create(marker_path(output)?)?;
+
+    writeln!(
+        writer,
+        "# Created by hagen. Lists the files produced by the last build."
+    )?;
+    for f in files {
+        writeln!(writer, "{}", f.to_string_lossy())?;
+    }
+
+    Ok(())
+}
+
+/// Read the list of files produced by the last build.
+pub fn read_marker<P: AsRef<Path>>(output: P) -> Result<BTreeSet<PathBuf>> {
+    let path = marker_path(output)?;
+    if !path.is_file() {
+        return Ok(BTreeSet::new());
+    }
+
+    let mut result = BTreeSet::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        result.insert(PathBuf::from(line));
+    }
+
+    Ok(result)
+}
+
+fn check_marker(output: &Path) -> Result<()> {
+    let marker = marker_path(output)?;
+    if marker.is_file() || fs::read_dir(output)?.next().is_none() {
+        return Ok(());
+    }
+
+    Err(GeneratorError::Error(format!(
+        "Refusing to clean output directory {:?}: it was not created by hagen (missing marker file {:?}). \
+         Remove the directory, or create an empty marker file if it only contains generated files.",
+        output, marker
+    ))
+    .into())
+}
+
+fn remove_empty_parents(output: &Path, path: &Path) -> Result<()> {
+    let mut current = path.parent();
+
+    while let Some(dir) = current {
+        if dir == output || !dir.starts_with(output) || fs::read_dir(dir)?.next().is_some() {
+            break;
+        }
+        debug!("Remove empty directory: {:?}", dir);
+        fs::remove_dir(dir)?;
+        current = dir.parent();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_marker_path() {
+        assert_eq!(
+            marker_path("site/output").unwrap(),
+            PathBuf::from("site/.output.hagen")
+        );
+        assert_eq!(
+            marker_path("/srv/www/").unwrap(),
+            PathBuf::from("/srv/.www.hagen")
+        );
+        assert!(marker_path("/").is_err());
+    }
+
+    /// Create an output directory with some files, in a temporary directory.
+    fn output(name: &str, files: &[&str]) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("hagen-clean-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+
+        let output = root.join("output");
+        fs::create_dir_all(&output).unwrap();
+        for file in files {
+            let path = output.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+
+        output
+    }
+
+    fn files(paths: &[&str]) -> BTreeSet<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn test_clean_all() {
+        let output = output("all", &["index.html", "css/main.css"]);
+        write_marker(&output, &files(&["index.html"])).unwrap();
+
+        clean_all(&output).unwrap();
+
+        assert!(output.is_dir());
+        assert!(snapshot(&output).unwrap().is_empty());
+        assert_eq!(read_marker(&output).unwrap(), files(&["index.html"]));
+
+        fs::remove_dir_all(output.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_clean_all_without_marker() {
+        let output = output("foreign", &["index.html"]);
+
+        let err = clean_all(&output).unwrap_err().to_string();
+        assert!(
+            err.starts_with("Refusing to clean output directory"),
+            "{}",
+            err
+        );
+        assert_eq!(snapshot(&output).unwrap(), files(&["index.html"]));
+
+        // empty directories are fine
+        fs::remove_file(output.join("index.html")).unwrap();
+        clean_all(&output).unwrap();
+        assert!(marker_path(&output).unwrap().is_file());
+
+        fs::remove_dir_all(output.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_clean_previous() {
+        let output = output(
+            "previous",
+            &["index.html", "posts/a.html", "css/main.css", "CNAME"],
+        );
+        write_marker(
+            &output,
+            &files(&["index.html", "posts/a.html", "gone.html"]),
+        )
+        .unwrap();
+
+        clean_previous(&output).unwrap();
+
+        assert_eq!(
+            snapshot(&output).unwrap(),
+            files(&["CNAME", "css/main.css"])
+        );
+        assert!(!output.join("posts").exists());
+
+        fs::remove_dir_all(output.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_adopt() {
+        let output = output("adopt", &["index.html", "posts/a.html"]);
+
+        adopt(&output).unwrap();
+        assert_eq!(
+            read_marker(&output).unwrap(),
+            files(&["index.html", "posts/a.html"])
+        );
+
+        // an existing marker is kept
+        write_marker(&output, &files(&["index.html"])).unwrap();
+        adopt(&output).unwrap();
+        clean_previous(&output).unwrap();
+        assert_eq!(snapshot(&output).unwrap(), files(&["posts/a.html"]));
+
+        fs::remove_dir_all(output.parent().unwrap()).unwrap();
+    }
+}
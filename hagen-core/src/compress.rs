@@ -0,0 +1,148 @@
+use failure::Error;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::{debug, info};
+use walkdir::WalkDir;
+
+use crate::rules::CompressOptions;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Write pre-compressed variants (`.gz`, `.br`) of the files in the output directory.
+///
+/// Returns the variants written, relative to the output directory.
+pub fn compress_dir<P: AsRef<Path>>(output: P, options: &CompressOptions) -> Result<Vec<PathBuf>> {
+    let output = output.as_ref();
+    info!("Compressing output: {:?}", output);
+
+    let mut written = Vec::new();
+
+    for entry in WalkDir::new(output).into_iter() {
+        let entry = entry?;
+        let path = entry.path();
+
+        if !entry.file_type().is_file() || !is_compressible(path, options) {
+            continue;
+        }
+
+        let data = fs::read(path)?;
+        if (data.len() as u64) < options.threshold {
+            debug!("Skipping small file: {:?}", path);
+            continue;
+        }
+
+        if options.gzip {
+            written.extend(write_variant(path, "gz", &data, gzip(&data)?, options)?);
+        }
+        if options.brotli {
+            written.extend(write_variant(path, "br", &data, brotli(&data)?, options)?);
+        }
+    }
+
+    info!("Wrote {} compressed file(s)", written.len());
+
+    Ok(written
+        .into_iter()
+        .filter_map(|p| p.strip_prefix(output).ok().map(PathBuf::from))
+        .collect())
+}
+
+fn is_compressible(path: &Path, options: &CompressOptions) -> bool {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => options
+            .extensions
+            .iter()
+            .any(|e| e.eq_ignore_ascii_case(ext)),
+        None => false,
+    }
+}
+
+/// Write a compressed variant, unless it doesn't save enough compared to the original.
+fn write_variant(
+    path: &Path,
+    extension: &str,
+    original: &[u8],
+    compressed: Vec<u8>,
+    options: &CompressOptions,
+) -> Result<Option<PathBuf>> {
+    let target = path.with_file_name(format!(
+        "{}.{}",
+        path.file_name().unwrap_or_default().to_string_lossy(),
+        extension
+    ));
+
+    if !compresses_well(original.len(), compressed.len(), options.max_ratio) {
+        debug!("Not compressing well ({}): {:?}", extension, path);
+        if target.is_file() {
+            fs::remove_file(&target)?;
+        }
+        return Ok(None);
+    }
+
+    debug!("Compressed: {:?}", target);
+    fs::write(&target, compressed)?;
+
+    Ok(Some(target))
+}
+
+fn compresses_well(original: usize, compressed: usize, max_ratio: f64) -> bool {
+    original > 0 && (compressed as f64) <= (original as f64) * max_ratio
+}
+
+fn gzip(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+fn brotli(data: &[u8]) -> Result<Vec<u8>> {
+    let mut result = Vec::new();
+    {
+        let mut encoder = brotli::CompressorWriter::new(&mut result, 4096, 11, 22);
+        encoder.write_all(data)?;
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compressible() {
+        let options = CompressOptions::default();
+        assert!(is_compressible(Path::new("index.html"), &options));
+        assert!(is_compressible(Path::new("css/site.CSS"), &options));
+        assert!(!is_compressible(Path::new("index.html.gz"), &options));
+        assert!(!is_compressible(Path::new("image.png"), &options));
+        assert!(!is_compressible(Path::new(".hagen"), &options));
+    }
+
+    #[test]
+    fn test_compresses_well() {
+        assert!(compresses_well(1000, 300, 0.9));
+        assert!(!compresses_well(1000, 950, 0.9));
+        assert!(!compresses_well(0, 0, 0.9));
+    }
+
+    #[test]
+    fn test_roundtrip() -> Result<()> {
+        let data = "<p>Hello World</p>\n".repeat(100);
+        let compressed = gzip(data.as_bytes())?;
+        assert!(compressed.len() < data.len());
+
+        let mut result = String::new();
+        std::io::Read::read_to_string(
+            &mut flate2::read::GzDecoder::new(compressed.as_slice()),
+            &mut result,
+        )?;
+        assert_eq!(result, data);
+
+        assert!(brotli(data.as_bytes())?.len() < data.len());
+        Ok(())
+    }
+}
@@ -1,21 +1,62 @@
 use failure::Error;
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use globset::{Glob, GlobSetBuilder};
 use log::debug;
 use walkdir::WalkDir;
 
+use crate::ignores::Ignores;
+
 type Result<T> = std::result::Result<T, Error>;
 
-pub fn copy_dir<P1, P2, S>(from: P1, to: P2, glob: Option<S>) -> Result<()>
+/// Copy a directory, returning the copied files, relative to the target directory.
+pub fn copy_dir<P1, P2, S>(
+    from: P1,
+    to: P2,
+    glob: Option<S>,
+    ignores: &Ignores,
+) -> Result<Vec<PathBuf>>
 where
     P1: AsRef<Path>,
     P2: AsRef<Path>,
     S: AsRef<str>,
 {
-    let from = from.as_ref();
     let to = to.as_ref();
+    let mut copied = Vec::new();
+
+    walk_dir(from, glob, ignores, |source, relative| {
+        let target = to.join(relative);
+
+        if source.is_file() {
+            debug!("Copy file - to: {:?}", target);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(source, target)?;
+            copied.push(relative.to_path_buf());
+        } else if source.is_dir() {
+            debug!("Create directory: {:?}", target);
+            fs::create_dir_all(target)?;
+        }
+
+        Ok(())
+    })?;
+
+    Ok(copied)
+}
+
+/// Walk a directory, calling the function for each file and directory matching the glob.
+///
+/// The function gets called with the full path, and the path relative to the directory.
+pub fn walk_dir<P, S, F>(from: P, glob: Option<S>, ignores: &Ignores, mut f: F) -> Result<()>
+where
+    P: AsRef<Path>,
+    S: AsRef<str>,
+    F: FnMut(&Path, &Path) -> Result<()>,
+{
+    let from = from.as_ref();
 
     let mut builder = GlobSetBuilder::new();
     if let Some(ref g) = glob {
@@ -23,7 +64,45 @@ where
     }
     let set = builder.build()?;
 
-    for item in WalkDir::new(&from).contents_first(false).follow_links(true) {
+    // ignore rules of all visited directories
+
+    let mut dirs: HashMap<PathBuf, Ignores> = HashMap::new();
+    dirs.insert(from.to_path_buf(), ignores.for_dir(from)?);
+    let mut error = None;
+
+    let walker = WalkDir::new(&from)
+        .contents_first(false)
+        .follow_links(true)
+        .into_iter()
+        .filter_entry(|entry| {
+            let path = entry.path();
+            let parent = match path.parent() {
+                Some(parent) if entry.depth() > 0 => parent,
+                _ => return true,
+            };
+            let is_dir = entry.file_type().is_dir();
+
+            if let Some(true) = dirs.get(parent).map(|i| i.is_ignored(path, is_dir)) {
+                debug!("Ignoring: {:?}", path);
+                return false;
+            }
+
+            if is_dir {
+                match dirs.get(parent).map(|i| i.for_dir(path)) {
+                    Some(Ok(ignores)) => {
+                        dirs.insert(path.to_path_buf(), ignores);
+                    }
+                    Some(Err(err)) => {
+                        error.get_or_insert(err);
+                    }
+                    None => {}
+                }
+            }
+
+            true
+        });
+
+    for item in walker {
         debug!("Found: {:?}", item);
         match item {
             Ok(entry) => {
@@ -31,23 +110,52 @@ where
                 let relative = source.strip_prefix(from)?;
 
                 if set.is_empty() || set.is_match(relative) {
-                    let target = to.join(relative);
-
-                    if source.is_file() {
-                        debug!("Copy file - to: {:?}", target);
-                        if let Some(parent) = target.parent() {
-                            fs::create_dir_all(parent)?;
-                        }
-                        fs::copy(source, target)?;
-                    } else if source.is_dir() {
-                        debug!("Create directory: {:?}", target);
-                        fs::create_dir_all(target)?;
-                    }
+                    f(source, relative)?;
                 }
             }
             Err(_) => {}
         }
     }
 
-    Ok(())
+    match error {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::IgnoreConfig;
+
+    #[test]
+    fn test_copy_hidden_assets() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("hagen-copy-{}", std::process::id()));
+        let from = dir.join("assets");
+        let to = dir.join("output");
+
+        fs::create_dir_all(from.join(".well-known"))?;
+        fs::write(from.join(".nojekyll"), "")?;
+        fs::write(from.join(".well-known/security.txt"), "Contact: foo@bar")?;
+        fs::write(from.join("site.css"), "")?;
+        fs::write(from.join(".DS_Store"), "")?;
+
+        let ignores = Ignores::new(&from, &IgnoreConfig::default())?
+            .with_hidden(&[".nojekyll".into(), ".well-known".into()]);
+        let mut copied = copy_dir(&from, &to, None::<&str>, &ignores)?;
+        copied.sort();
+
+        fs::remove_dir_all(&dir)?;
+
+        assert_eq!(
+            copied,
+            vec![
+                PathBuf::from(".nojekyll"),
+                PathBuf::from(".well-known/security.txt"),
+                PathBuf::from("site.css"),
+            ]
+        );
+
+        Ok(())
+    }
 }
@@ -0,0 +1,273 @@
+use failure::{Error, Fail};
+use handlebars::{RenderError, TemplateError, TemplateFileError, TemplateRenderError};
+use serde::Serialize;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::error::GeneratorError;
+
+/// A report of an error, including the context it occurred in.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    /// The main error message.
+    pub message: String,
+    /// The messages of the causing errors.
+    pub causes: Vec<String>,
+    /// The rule which was processed.
+    pub rule: Option<String>,
+    /// The content file which was processed.
+    pub content: Option<String>,
+    /// The name of the template which was processed.
+    pub template: Option<String>,
+    /// The file the error is located in.
+    pub file: Option<PathBuf>,
+    /// The line (one-based) in the file.
+    pub line: Option<usize>,
+    /// The column (one-based) in the file.
+    pub column: Option<usize>,
+}
+
+impl Diagnostic {
+    /// Create a diagnostic by evaluating the chain of causes of an error.
+    pub fn from_error(err: &Error) -> Self {
+        let mut result = Diagnostic::default();
+        let mut messages: Vec<String> = Vec::new();
+
+        for cause in err.iter_chain() {
+            let message = Self::message(cause);
+            if messages.last() != Some(&message) {
+                messages.push(message);
+            }
+            result.evaluate(cause);
+        }
+
+        let mut messages = messages.into_iter();
+        result.message = messages.next().unwrap_or_default();
+        result.causes = messages.collect();
+
+        result
+    }
+
+    /// Get the message of a cause, without the source snippet of template errors, which is
+    /// part of the report anyway.
+    fn message(cause: &dyn Fail) -> String {
+        let err = match cause.downcast_ref::<GeneratorError>() {
+            Some(GeneratorError::TemplateError(TemplateFileError::TemplateError(err))) => Some(err),
+            Some(GeneratorError::TemplateRenderError(TemplateRenderError::TemplateError(err))) => {
+                Some(err)
+            }
+            _ => None,
+        };
+        let err = err
+            .or_else(|| match cause.downcast_ref::<TemplateFileError>() {
+                Some(TemplateFileError::TemplateError(err)) => Some(err),
+                _ => None,
+            })
+            .or_else(|| match cause.downcast_ref::<TemplateRenderError>() {
+                Some(TemplateRenderError::TemplateError(err)) => Some(err),
+                _ => None,
+            })
+            .or_else(|| cause.downcast_ref::<TemplateError>());
+
+        match err {
+            Some(err) => format!("Template error: {}", err.reason),
+            None => cause.to_string(),
+        }
+    }
+
+    fn evaluate(&mut self, cause: &dyn Fail) {
+        if let Some(err) = cause.downcast_ref::<GeneratorError>() {
+            match err {
+                GeneratorError::Render {
+                    rule,
+                    content,
+                    template_file,
+                    ..
+                } => {
+                    self.rule = Some(rule.clone());
+                    self.content = Some(content.clone());
+                    if template_file.is_some() {
+                        self.file = template_file.clone();
+                    }
+                }
+                GeneratorError::Content {
+                    path, line, column, ..
+                } => {
+                    self.content = Some(path.clone());
+                    self.file = Some(path.into());
+                    self.location(*line, *column);
+                }
+                GeneratorError::Template {
+                    template,
+                    file,
+                    line,
+                    column,
+                    ..
+                } => {
+                    if template.is_some() {
+                        self.template = template.clone();
+                    }
+                    if file.is_some() {
+                        self.file = file.clone();
+                    }
+                    self.location(*line, *column);
+                }
+                GeneratorError::TemplateError(TemplateFileError::TemplateError(err)) => {
+                    self.template_error(err)
+                }
+                GeneratorError::TemplateRenderError(err) => self.template_render_error(err),
+                _ => {}
+            }
+        } else if let Some(err) = cause.downcast_ref::<TemplateRenderError>() {
+            self.template_render_error(err);
+        } else if let Some(TemplateFileError::TemplateError(err)) =
+            cause.downcast_ref::<TemplateFileError>()
+        {
+            self.template_error(err);
+        } else if let Some(err) = cause.downcast_ref::<TemplateError>() {
+            self.template_error(err);
+        } else if let Some(err) = cause.downcast_ref::<RenderError>() {
+            self.render_error(err);
+        }
+    }
+
+    fn template_render_error(&mut self, err: &TemplateRenderError) {
+        match err {
+            TemplateRenderError::TemplateError(err) => self.template_error(err),
+            TemplateRenderError::RenderError(err) => self.render_error(err),
+            _ => {}
+        }
+    }
+
+    fn template_error(&mut self, err: &TemplateError) {
+        if err.template_name.is_some() {
+            self.template = err.template_name.clone();
+        }
+        self.location(err.line_no, err.column_no);
+    }
+
+    fn render_error(&mut self, err: &RenderError) {
+        if err.template_name.is_some() {
+            self.template = err.template_name.clone();
+        }
+        self.location(err.line_no, err.column_no);
+    }
+
+    fn location(&mut self, line: Option<usize>, column: Option<usize>) {
+        if line.is_some() {
+            self.line = line;
+            self.column = column;
+        }
+    }
+
+    /// Create a human readable report, including a snippet of the source.
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+
+        let _ = writeln!(out, "error: {}", self.message);
+
+        if let Some(ref file) = self.file {
+            match (self.line, self.column) {
+                (Some(line), Some(column)) => {
+                    let _ = writeln!(out, "  --> {}:{}:{}", file.display(), line, column);
+                }
+                (Some(line), None) => {
+                    let _ = writeln!(out, "  --> {}:{}", file.display(), line);
+                }
+                _ => {
+                    let _ = writeln!(out, "  --> {}", file.display());
+                }
+            }
+
+            if let Some(snippet) = self.snippet() {
+                out.push_str(&snippet);
+            }
+        }
+
+        if let Some(ref rule) = self.rule {
+            let _ = writeln!(out, "   = rule: {}", rule);
+        }
+        if let Some(ref content) = self.content {
+            let _ = writeln!(out, "   = content: {}", content);
+        }
+        if let Some(ref template) = self.template {
+            let _ = writeln!(out, "   = template: {}", template);
+        }
+        if self.file.is_none() {
+            if let Some(line) = self.line {
+                let _ = writeln!(
+                    out,
+                    "   = location: line {}, column {}",
+                    line,
+                    self.column.unwrap_or(1)
+                );
+            }
+        }
+
+        for cause in &self.causes {
+            let _ = writeln!(out, "caused by: {}", cause);
+        }
+
+        out
+    }
+
+    fn snippet(&self) -> Option<String> {
+        let line = self.line?;
+        let source = fs::read_to_string(self.file.as_ref()?).ok()?;
+        // unlike `lines()`, this includes the empty last line of errors at the end of the file
+        let text = source.split('\n').nth(line.checked_sub(1)?)?;
+        let text = text.trim_end_matches('\r');
+
+        let number = line.to_string();
+        let pad = " ".repeat(number.len());
+
+        let mut out = String::new();
+        let _ = writeln!(out, "{} |", pad);
+        let _ = writeln!(out, "{} | {}", number, text);
+        if let Some(column) = self.column {
+            let _ = writeln!(out, "{} | {}^", pad, " ".repeat(column.saturating_sub(1)));
+        }
+
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain() {
+        let err: Error = GeneratorError::Render {
+            rule: "layout".into(),
+            content: "/index.md".into(),
+            template_file: None,
+            cause: GeneratorError::Error("Missing value".into()).into(),
+        }
+        .into();
+
+        let d = Diagnostic::from_error(&err);
+
+        assert_eq!(d.message, "Failed to render '/index.md' with rule 'layout'");
+        assert_eq!(d.causes, vec!["Missing value"]);
+        assert_eq!(d.rule.as_deref(), Some("layout"));
+        assert_eq!(d.content.as_deref(), Some("/index.md"));
+    }
+
+    #[test]
+    fn test_render_error_location() {
+        let mut cause = RenderError::new("Failed");
+        cause.template_name = Some("default".into());
+        cause.line_no = Some(3);
+        cause.column_no = Some(7);
+
+        let err: Error = GeneratorError::GenericError(cause.into()).into();
+        let d = Diagnostic::from_error(&err);
+
+        assert_eq!(d.template.as_deref(), Some("default"));
+        assert_eq!(d.line, Some(3));
+        assert_eq!(d.column, Some(7));
+    }
+}
@@ -0,0 +1,126 @@
+use std::io::Write;
+use std::path::Path;
+
+use handlebars::{Handlebars, TemplateFileError};
+use serde_json::Value;
+
+use crate::engine::TemplateEngine;
+use crate::error::GeneratorError;
+
+type Result<T> = std::result::Result<T, GeneratorError>;
+
+/// Template engine, backed by Handlebars. Templates use the extension `.hbs`.
+pub struct HandlebarsEngine<'a> {
+    handlebars: Handlebars<'a>,
+}
+
+impl<'a> HandlebarsEngine<'a> {
+    pub fn new(handlebars: Handlebars<'a>) -> Self {
+        HandlebarsEngine { handlebars }
+    }
+
+    /// Remove all registered templates, keeping the registered helpers.
+    pub fn clear_templates(&mut self) {
+        self.handlebars.clear_templates();
+    }
+}
+
+/// Locate a syntax error of a template in the template directory.
+fn template_file_error(dir: &Path, err: TemplateFileError) -> GeneratorError {
+    match err {
+        TemplateFileError::TemplateError(err) => GeneratorError::Template {
+            message: format!("Template error: {}", err.reason),
+            template: err.template_name.clone(),
+            file: err
+                .template_name
+                .as_ref()
+                .map(|name| dir.join(format!("{}.hbs", name))),
+            line: err.line_no,
+            column: err.column_no,
+            cause: err.into(),
+        },
+        err => err.into(),
+    }
+}
+
+impl<'a> TemplateEngine for HandlebarsEngine<'a> {
+    fn register_templates_directory(&mut self, dir: &Path) -> Result<()> {
+        self.handlebars
+            .register_templates_directory(".hbs", dir)
+            .map_err(|err| template_file_error(dir, err))
+    }
+
+    fn has_template(&self, name: &str) -> bool {
+        self.handlebars.has_template(name)
+    }
+
+    fn render_to_write(&self, name: &str, data: &Value, writer: &mut dyn Write) -> Result<()> {
+        self.handlebars.render_to_write(name, data, writer)?;
+        Ok(())
+    }
+
+    fn render_template(&mut self, template: &str, data: &Value) -> Result<String> {
+        Ok(self.handlebars.render_template(template, data)?)
+    }
+
+    fn render_template_to_write(
+        &mut self,
+        template: &str,
+        data: &Value,
+        writer: &mut dyn Write,
+    ) -> Result<()> {
+        self.handlebars
+            .render_template_to_write(template, data, writer)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::Diagnostic;
+    use std::fs;
+
+    fn register_error(name: &str, template: &str) -> Result<(Diagnostic, String)> {
+        let dir = std::env::temp_dir().join(format!("hagen-hbs-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("note.hbs"), template)?;
+
+        let mut engine = HandlebarsEngine::new(Handlebars::new());
+        let err: failure::Error = engine
+            .register_templates_directory(&dir)
+            .expect_err("Must fail")
+            .into();
+        let diagnostic = Diagnostic::from_error(&err);
+        let report = diagnostic.report();
+        fs::remove_dir_all(&dir)?;
+
+        assert_eq!(diagnostic.template, Some("note".into()));
+        assert_eq!(diagnostic.file, Some(dir.join("note.hbs")));
+        Ok((diagnostic, report))
+    }
+
+    #[test]
+    fn test_syntax_error() -> Result<()> {
+        let (d, report) = register_error("syntax", "<p>\n  {{foo bar=}}\n</p>\n")?;
+        assert_eq!(d.line, Some(2));
+        assert!(
+            report.contains("2 |   {{foo bar=}}\n  | "),
+            "Unexpected report: {}",
+            report
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_syntax_error_at_end() -> Result<()> {
+        let (d, report) = register_error("end", "<p>\n{{#if foo}}\n</p>\n")?;
+        assert_eq!((d.line, d.column), (Some(4), Some(1)));
+        assert!(
+            report.contains("4 | \n  | ^"),
+            "Unexpected report: {}",
+            report
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,50 @@
+use std::io::Write;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::GeneratorError;
+
+pub mod handlebars;
+pub mod tera;
+
+type Result<T> = std::result::Result<T, GeneratorError>;
+
+/// The template engine to use for a site.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Engine {
+    #[default]
+    Handlebars,
+    Tera,
+}
+
+/// A template engine, rendering pages as well as inline templates of the configuration.
+pub trait TemplateEngine {
+    /// Register all templates of a directory.
+    ///
+    /// Templates registered later override existing templates with the same name.
+    fn register_templates_directory(&mut self, dir: &Path) -> Result<()>;
+
+    /// Check if a template with that name is registered.
+    fn has_template(&self, name: &str) -> bool;
+
+    /// Render a registered template.
+    fn render_to_write(&self, name: &str, data: &Value, writer: &mut dyn Write) -> Result<()>;
+
+    /// Render a template string.
+    fn render_template(&mut self, template: &str, data: &Value) -> Result<String>;
+
+    /// Render a template string.
+    fn render_template_to_write(
+        &mut self,
+        template: &str,
+        data: &Value,
+        writer: &mut dyn Write,
+    ) -> Result<()> {
+        let result = self.render_template(template, data)?;
+        writer.write_all(result.as_bytes())?;
+        Ok(())
+    }
+}
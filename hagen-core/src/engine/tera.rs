@@ -0,0 +1,563 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+use log::debug;
+use regex::Regex;
+use serde_json::Value;
+use tera::{Context, Tera};
+use url::Url;
+use walkdir::WalkDir;
+
+use crate::engine::TemplateEngine;
+use crate::error::GeneratorError;
+use crate::generator::GeneratorContextProvider;
+use crate::helper::asset::{asset_integrity, asset_url};
+use crate::helper::i18n::translate;
+use crate::helper::images::process_image;
+use crate::helper::markdown;
+use crate::helper::sort::{sorted_array, sorted_map};
+use crate::helper::time::{format_timestamp, TimestampOptions};
+use crate::helper::url::full_url_from;
+
+type Result<T> = std::result::Result<T, GeneratorError>;
+
+lazy_static! {
+    static ref RE_PARSE: Regex = Regex::new(r#"^Failed to parse "(.*)""#).unwrap();
+    static ref RE_POSITION: Regex = Regex::new(r"--> (\d+):(\d+)").unwrap();
+    static ref RE_REASON: Regex = Regex::new(r"(?m)^\s*= (.*)$").unwrap();
+    static ref RE_VARIABLE: Regex = Regex::new(r"Variable `([^`]+)` not found").unwrap();
+}
+
+/// Template engine, backed by Tera. Templates use the extension `.tera`, which is stripped
+/// from the template name. So `templates/default.html.tera` is registered as `default.html`.
+pub struct TeraEngine {
+    tera: Tera,
+}
+
+impl TeraEngine {
+    pub fn new(context: GeneratorContextProvider, default_helpers: bool) -> Self {
+        let mut tera = Tera::default();
+
+        if default_helpers {
+            register_helpers(&mut tera, context);
+        }
+
+        TeraEngine { tera }
+    }
+
+    /// Convert an error of Tera, which only reports positions for syntax errors.
+    ///
+    /// For missing variables, the position of the first use of the variable is reported.
+    fn template_error(&self, template: Option<&str>, err: tera::Error) -> GeneratorError {
+        let message = err.to_string();
+
+        let mut causes = Vec::new();
+        let mut source = std::error::Error::source(&err);
+        while let Some(cause) = source {
+            causes.push(cause.to_string());
+            source = cause.source();
+        }
+
+        let mut file = template
+            .and_then(|name| self.tera.get_template(name).ok())
+            .and_then(|t| t.path.as_ref())
+            .map(PathBuf::from);
+        if let Some(cap) = RE_PARSE.captures(&message) {
+            file = Some(PathBuf::from(&cap[1]));
+        }
+
+        let mut location = None;
+        for cause in &mut causes {
+            if let Some(cap) = RE_POSITION.captures(cause) {
+                location = Some((cap[1].parse().ok(), cap[2].parse().ok()));
+                // reduce the source snippet to the reason
+                if let Some(reason) = RE_REASON.captures(cause).map(|cap| cap[1].to_string()) {
+                    *cause = reason;
+                }
+            } else if let Some(cap) = RE_VARIABLE.captures(cause) {
+                location = file
+                    .as_ref()
+                    .and_then(|file| fs::read_to_string(file).ok())
+                    .and_then(|source| find_position(&source, &cap[1]))
+                    .map(|(line, column)| (Some(line), Some(column)));
+            }
+        }
+        let (line, column) = location.unwrap_or_default();
+
+        let mut cause: failure::Error =
+            GeneratorError::Error(causes.pop().unwrap_or_else(|| message.clone())).into();
+        while let Some(message) = causes.pop() {
+            cause = GeneratorError::GenericDetailError(cause, message).into();
+        }
+
+        GeneratorError::Template {
+            message,
+            template: template.map(|t| t.into()),
+            file,
+            line,
+            column,
+            cause,
+        }
+    }
+}
+
+/// Find the one-based line and column of the first occurrence of a string.
+fn find_position(source: &str, s: &str) -> Option<(usize, usize)> {
+    source
+        .lines()
+        .enumerate()
+        .find_map(|(n, line)| line.find(s).map(|c| (n + 1, line[..c].chars().count() + 1)))
+}
+
+impl TemplateEngine for TeraEngine {
+    fn register_templates_directory(&mut self, dir: &Path) -> Result<()> {
+        let mut files = Vec::new();
+
+        for entry in WalkDir::new(dir).follow_links(true) {
+            let entry = entry.map_err(|err| GeneratorError::GenericError(err.into()))?;
+            let path = entry.path();
+
+            let relative = path
+                .strip_prefix(dir)
+                .map_err(|err| GeneratorError::GenericError(err.into()))?;
+
+            let name = match relative.to_str() {
+                Some(name) if entry.file_type().is_file() && name.ends_with(".tera") => {
+                    name.trim_end_matches(".tera").replace('\\', "/")
+                }
+                _ => continue,
+            };
+
+            debug!("Register template: {} -> {:?}", name, path);
+            files.push((path.to_path_buf(), Some(name)));
+        }
+
+        self.tera
+            .add_template_files(files)
+            .map_err(|err| self.template_error(None, err))?;
+
+        Ok(())
+    }
+
+    fn has_template(&self, name: &str) -> bool {
+        self.tera.get_template_names().any(|n| n == name)
+    }
+
+    fn render_to_write(&self, name: &str, data: &Value, writer: &mut dyn Write) -> Result<()> {
+        let result = self
+            .tera
+            .render(name, &Context::from_serialize(data)?)
+            .map_err(|err| self.template_error(Some(name), err))?;
+        writer.write_all(result.as_bytes())?;
+        Ok(())
+    }
+
+    fn render_template(&mut self, template: &str, data: &Value) -> Result<String> {
+        let context = Context::from_serialize(data)?;
+        self.tera
+            .render_str(template, &context)
+            .map_err(|err| self.template_error(None, err))
+    }
+}
+
+fn register_helpers(tera: &mut Tera, context: GeneratorContextProvider) {
+    // functions
+
+    let ctx = context.clone();
+    tera.register_function("absolute_url", move |args: &HashMap<String, Value>| {
+        let url = string_arg(args, "url")?;
+        let url = ctx
+            .with(|context| Ok(full_url_from(&url, &context.output)?))
+            .map_err(tera_error)?;
+        Ok(Value::String(url.into()))
+    });
+
+    let ctx = context.clone();
+    tera.register_function("relative_url", move |args: &HashMap<String, Value>| {
+        let url = string_arg(args, "url")?;
+        if let Ok(url) = Url::parse(&url) {
+            return Ok(Value::String(url.into()));
+        }
+        let url = ctx
+            .with(|context| Ok(full_url_from(&url, &context.output)?))
+            .map_err(tera_error)?;
+        Ok(Value::String(url.path().into()))
+    });
+
+    let ctx = context.clone();
+    tera.register_function("asset_url", move |args: &HashMap<String, Value>| {
+        let path = string_arg(args, "path")?;
+        let url = ctx
+            .with(|context| Ok(asset_url(context, &path)?))
+            .map_err(tera_error)?;
+        Ok(Value::String(url))
+    });
+
+    let ctx = context.clone();
+    tera.register_function("asset_integrity", move |args: &HashMap<String, Value>| {
+        let path = string_arg(args, "path")?;
+        let integrity = ctx
+            .with(|context| Ok(asset_integrity(context, &path)?))
+            .map_err(tera_error)?;
+        Ok(Value::String(integrity))
+    });
+
+    let ctx = context.clone();
+    tera.register_function("image", move |args: &HashMap<String, Value>| {
+        let path = string_arg(args, "path")?;
+        let image = ctx
+            .with(|context| Ok(process_image(context, &path)?))
+            .map_err(tera_error)?;
+        Ok(serde_json::to_value(image)?)
+    });
+
+    let ctx = context.clone();
+    tera.register_function("t", move |args: &HashMap<String, Value>| {
+        let key = string_arg(args, "key")?;
+        let args: serde_json::Map<String, Value> = args
+            .iter()
+            .filter(|(k, _)| k.as_str() != "key")
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        let message = ctx
+            .with(|context| Ok(translate(context, &key, &args)?))
+            .map_err(tera_error)?;
+        Ok(Value::String(message))
+    });
+
+    let ctx = context.clone();
+    tera.register_function("active", move |args: &HashMap<String, Value>| {
+        let url = string_arg(args, "url")?;
+        let (check_url, page_url) = ctx
+            .with(|context| {
+                Ok((
+                    full_url_from(&url, &context.output)?,
+                    full_url_from("", &context.output)?,
+                ))
+            })
+            .map_err(tera_error)?;
+
+        if check_url == page_url {
+            let value = args
+                .get("value")
+                .and_then(|v| v.as_str())
+                .unwrap_or("active");
+            Ok(Value::String(value.into()))
+        } else {
+            Ok(Value::String(String::new()))
+        }
+    });
+
+    let ctx = context.clone();
+    tera.register_function("timestamp", move |args: &HashMap<String, Value>| {
+        let format = string_arg(args, "format")?;
+        let options = timestamp_options(&ctx, args)?;
+        let result = format_timestamp(&format, args.get("value"), &options).map_err(tera_error)?;
+        Ok(Value::String(result))
+    });
+
+    // filters
+
+    let ctx = context;
+    tera.register_filter(
+        "timestamp",
+        move |value: &Value, args: &HashMap<String, Value>| {
+            let format = string_arg(args, "format")?;
+            let options = timestamp_options(&ctx, args)?;
+            let result = format_timestamp(&format, Some(value), &options).map_err(tera_error)?;
+            Ok(Value::String(result))
+        },
+    );
+
+    tera.register_filter(
+        "markdownify",
+        |value: &Value, _: &HashMap<String, Value>| {
+            let input = value
+                .as_str()
+                .ok_or_else(|| tera::Error::msg("Require string data for markdownify"))?;
+            let result = markdown::render(input).map_err(tera_error)?;
+            Ok(Value::String(result))
+        },
+    );
+
+    tera.register_filter("sorted", |value: &Value, args: &HashMap<String, Value>| {
+        let path = string_arg(args, "path")?;
+        let invert = args
+            .get("invert")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        match value {
+            Value::Array(list) => Ok(Value::Array(
+                sorted_array(list, &path, invert)
+                    .into_iter()
+                    .map(|i| list[i].clone())
+                    .collect(),
+            )),
+            Value::Object(map) => Ok(Value::Array(
+                sorted_map(map, &path, invert)
+                    .into_iter()
+                    .map(|k| {
+                        let mut entry = serde_json::Map::new();
+                        entry.insert("key".into(), Value::String(k.clone()));
+                        entry.insert("value".into(), map[k.as_str()].clone());
+                        Value::Object(entry)
+                    })
+                    .collect(),
+            )),
+            _ => Err(tera::Error::msg(format!(
+                "Value is not iterable: {:?}",
+                value
+            ))),
+        }
+    });
+}
+
+fn string_arg(args: &HashMap<String, Value>, name: &str) -> tera::Result<String> {
+    args.get(name)
+        .ok_or_else(|| tera::Error::msg(format!("Missing argument '{}'", name)))?
+        .as_str()
+        .map(|s| s.to_string())
+        .ok_or_else(|| tera::Error::msg(format!("Argument '{}' must be a string", name)))
+}
+
+fn timestamp_options(
+    context: &GeneratorContextProvider,
+    args: &HashMap<String, Value>,
+) -> tera::Result<TimestampOptions> {
+    let optional = |name: &str| match args.get(name) {
+        Some(_) => string_arg(args, name).map(Some),
+        None => Ok(None),
+    };
+
+    let options = TimestampOptions {
+        locale: optional("locale")?,
+        tz: optional("tz")?,
+        relative: args
+            .get("relative")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+        ..Default::default()
+    };
+
+    context
+        .with_optional(|context| Ok(options.with_context(context)))
+        .map_err(tera_error)
+}
+
+fn tera_error<E: std::fmt::Display>(err: E) -> tera::Error {
+    tera::Error::msg(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::{GeneratorConfig, GeneratorContext, Output};
+    use serde_json::json;
+    use std::sync::{Arc, RwLock};
+
+    /// Create an engine for a page, with templates of a temporary directory.
+    fn engine(name: &str, templates: &[(&str, &str)]) -> Result<TeraEngine> {
+        let dir = std::env::temp_dir().join(format!("hagen-tera-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir)?;
+        for (name, content) in templates {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap())?;
+            fs::write(path, content)?;
+        }
+
+        let provider = Arc::new(RwLock::new(None));
+        let config = GeneratorConfig::for_test("http://localhost/base/");
+        let output = Output::new(config.basename.to_string(), "/foo/bar", None::<String>)?;
+        provider
+            .write()
+            .unwrap()
+            .replace(GeneratorContext::new(&config, &output));
+
+        let mut engine = TeraEngine::new(GeneratorContextProvider::new(&provider), true);
+        let result = engine.register_templates_directory(&dir);
+        fs::remove_dir_all(&dir)?;
+        result.map(|_| engine)
+    }
+
+    fn render(engine: &TeraEngine, name: &str, data: Value) -> Result<String> {
+        let mut result = Vec::new();
+        engine.render_to_write(name, &data, &mut result)?;
+        Ok(String::from_utf8(result).unwrap())
+    }
+
+    #[test]
+    fn test_template_names() -> Result<()> {
+        let engine = engine(
+            "names",
+            &[
+                ("default.html.tera", "{{ title }}"),
+                ("partials/nav.html.tera", "nav"),
+                ("README.md", "not a template"),
+            ],
+        )?;
+
+        assert!(engine.has_template("default.html"));
+        assert!(engine.has_template("partials/nav.html"));
+        assert!(!engine.has_template("default.html.tera"));
+        assert!(!engine.has_template("README.md"));
+        assert_eq!(
+            render(&engine, "default.html", json!({"title": "Foo"}))?,
+            "Foo"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_extends() -> Result<()> {
+        let engine = engine(
+            "extends",
+            &[
+                (
+                    "base.html.tera",
+                    "<title>{% block title %}Site{% endblock %}</title>",
+                ),
+                (
+                    "page.html.tera",
+                    "{% extends \"base.html\" %}{% block title %}{{ title }} - {{ super() }}{% endblock %}",
+                ),
+            ],
+        )?;
+
+        assert_eq!(
+            render(&engine, "page.html", json!({"title": "Foo"}))?,
+            "<title>Foo - Site</title>"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_functions() -> Result<()> {
+        let mut engine = engine("functions", &[])?;
+        let data = json!({});
+
+        assert_eq!(
+            engine.render_template(r#"{{ absolute_url(url="baz") }}"#, &data)?,
+            "http://localhost/base/foo/baz"
+        );
+        assert_eq!(
+            engine.render_template(r#"{{ relative_url(url="/baz") }}"#, &data)?,
+            "/base/baz"
+        );
+        assert_eq!(
+            engine.render_template(r#"{{ relative_url(url="https://foo.bar/") }}"#, &data)?,
+            "https://foo.bar/"
+        );
+        assert_eq!(
+            engine.render_template(r#"{{ active(url="/foo/bar") }}"#, &data)?,
+            "active"
+        );
+        assert_eq!(
+            engine.render_template(r#"{{ active(url="/foo/baz", value="current") }}"#, &data)?,
+            ""
+        );
+        assert_eq!(
+            engine.render_template(
+                r#"{{ timestamp(format="%Y-%m-%d", value="2020-04-21T10:30:00Z") }}"#,
+                &data
+            )?,
+            "2020-04-21"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_filters() -> Result<()> {
+        let mut engine = engine("filters", &[])?;
+        let data = json!({
+            "date": "2020-03-21",
+            "text": "Some *text*",
+            "posts": [{"title": "B"}, {"title": "A"}],
+            "pages": {"x": {"title": "B"}, "y": {"title": "A"}},
+        });
+
+        assert_eq!(
+            engine.render_template(
+                r#"{{ date | timestamp(format="%e. %B", locale="de") }}"#,
+                &data
+            )?,
+            "21. März"
+        );
+        assert_eq!(
+            engine.render_template(r#"{{ text | markdownify | safe }}"#, &data)?,
+            "<p>Some <em>text</em></p>\n"
+        );
+        assert_eq!(
+            engine.render_template(
+                r#"{% for p in posts | sorted(path="/title") %}{{ p.title }}{% endfor %}"#,
+                &data
+            )?,
+            "AB"
+        );
+        assert_eq!(
+            engine.render_template(
+                r#"{% for e in pages | sorted(path="/title", invert=true) %}{{ e.key }}{% endfor %}"#,
+                &data
+            )?,
+            "xy"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        let result = engine("syntax", &[("page.html.tera", "<p>\n{% if foo %}\n</p>\n")]);
+
+        match result.map(|_| ()).expect_err("Must fail") {
+            GeneratorError::Template {
+                file, line, column, ..
+            } => {
+                assert_eq!(
+                    file.and_then(|f| f.file_name().map(|n| n.to_owned())),
+                    Some("page.html.tera".into())
+                );
+                assert_eq!((line, column), (Some(4), Some(1)));
+            }
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_missing_variable() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("hagen-tera-missing-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        fs::write(
+            dir.join("page.html.tera"),
+            "<h1>{{ title }}</h1>\n<p>{{ missing }}</p>\n",
+        )?;
+
+        let provider = Arc::new(RwLock::new(None));
+        let mut engine = TeraEngine::new(GeneratorContextProvider::new(&provider), false);
+        engine.register_templates_directory(&dir)?;
+        let result = render(&engine, "page.html", json!({"title": "Foo"}));
+        fs::remove_dir_all(&dir)?;
+
+        match result.expect_err("Must fail") {
+            GeneratorError::Template {
+                template,
+                line,
+                column,
+                ..
+            } => {
+                assert_eq!(template, Some("page.html".into()));
+                assert_eq!((line, column), (Some(2), Some(7)));
+            }
+            err => panic!("Unexpected error: {:?}", err),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_position() {
+        assert_eq!(find_position("foo\n  bar", "bar"), Some((2, 3)));
+        assert_eq!(find_position("foo", "bar"), None);
+    }
+}
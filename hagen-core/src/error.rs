@@ -1,28 +1,58 @@
 use failure::{Error, Fail};
 use handlebars::{RenderError, TemplateFileError, TemplateRenderError};
 use jsonpath_lib::JsonPathError;
+use std::path::PathBuf;
 use url::ParseError;
 
 #[derive(Debug, Fail)]
 pub enum GeneratorError {
-    #[fail(display = "Failed to process")]
+    #[fail(display = "{}", _0)]
     GenericError(#[cause] Error),
     #[fail(display = "{}", _1)]
     GenericDetailError(#[cause] Error, String),
-    #[fail(display = "Failed to process")]
+    #[fail(display = "JSON error: {}", _0)]
     JsonError(#[cause] serde_json::Error),
-    #[fail(display = "Failed to process")]
+    #[fail(display = "YAML error: {}", _0)]
     YamlError(#[cause] serde_yaml::Error),
-    #[fail(display = "Failed to process")]
+    #[fail(display = "{}", _0)]
     TemplateError(#[cause] TemplateFileError),
-    #[fail(display = "Failed to process")]
+    #[fail(display = "{}", _0)]
     TemplateRenderError(#[cause] TemplateRenderError),
-    #[fail(display = "Failed to process")]
+    #[fail(display = "I/O error: {}", _0)]
     IoError(#[cause] std::io::Error),
     #[fail(display = "{}", _0)]
     Error(String),
     #[fail(display = "JSON path error: {}", _0)]
     JsonPath(String),
+    /// Failed to load a content file.
+    #[fail(display = "Failed to load content: {}", path)]
+    Content {
+        path: String,
+        line: Option<usize>,
+        column: Option<usize>,
+        #[cause]
+        cause: Error,
+    },
+    /// Failed to parse or render a template, located in the template source where possible.
+    #[fail(display = "{}", message)]
+    Template {
+        message: String,
+        template: Option<String>,
+        file: Option<PathBuf>,
+        line: Option<usize>,
+        column: Option<usize>,
+        #[cause]
+        cause: Error,
+    },
+    /// Failed to render an entry selected by a rule.
+    #[fail(display = "Failed to render '{}' with rule '{}'", content, rule)]
+    Render {
+        rule: String,
+        content: String,
+        template_file: Option<PathBuf>,
+        #[cause]
+        cause: Error,
+    },
 }
 
 impl From<GeneratorError> for RenderError {
@@ -73,6 +103,12 @@ impl From<handlebars::RenderError> for GeneratorError {
     }
 }
 
+impl From<tera::Error> for GeneratorError {
+    fn from(err: tera::Error) -> Self {
+        GeneratorError::GenericError(err.into())
+    }
+}
+
 impl From<ParseError> for GeneratorError {
     fn from(err: ParseError) -> Self {
         GeneratorError::GenericError(err.into())
@@ -0,0 +1,128 @@
+use failure::Error;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha384};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// The name of the manifest file, in the root of the output directory.
+pub const MANIFEST_FILE: &str = "assets-manifest.json";
+
+/// The number of hex digits of the content hash, added to the file name.
+const HASH_LEN: usize = 8;
+
+/// An entry of the asset manifest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestEntry {
+    /// The path of the fingerprinted file, relative to the site.
+    pub path: String,
+    /// The Subresource Integrity value of the file.
+    pub integrity: String,
+}
+
+/// Maps logical asset paths (e.g. `/assets/custom.css`) to their fingerprinted files.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Manifest {
+    assets: BTreeMap<String, ManifestEntry>,
+}
+
+impl Manifest {
+    pub fn is_empty(&self) -> bool {
+        self.assets.is_empty()
+    }
+
+    pub fn insert<S: AsRef<str>>(&mut self, path: S, entry: ManifestEntry) {
+        self.assets.insert(site_path(path), entry);
+    }
+
+    /// Look up a logical asset path. Paths are relative to the site, the leading slash is optional.
+    pub fn get<S: AsRef<str>>(&self, path: S) -> Option<&ManifestEntry> {
+        self.assets.get(&site_path(path))
+    }
+
+    /// Write the manifest file into the output directory.
+    pub fn write<P: AsRef<Path>>(&self, output: P) -> Result<()> {
+        let target = output.as_ref().join(MANIFEST_FILE);
+        info!("Writing asset manifest: {:?}", target);
+        serde_json::to_writer_pretty(File::create(target)?, self)?;
+        Ok(())
+    }
+}
+
+/// Rename a file, adding the hash of its content to the file name.
+///
+/// Returns the new path, and the Subresource Integrity value of the file.
+pub fn fingerprint_file<P: AsRef<Path>>(path: P) -> Result<(PathBuf, String)> {
+    let path = path.as_ref();
+    let data = fs::read(path)?;
+
+    let hash = format!("{:x}", Sha256::digest(&data));
+    let integrity = format!("sha384-{}", base64::encode(Sha384::digest(&data)));
+
+    let target = fingerprinted_path(path, &hash[..HASH_LEN]);
+    debug!("Fingerprint: {:?} -> {:?}", path, target);
+    fs::rename(path, &target)?;
+
+    Ok((target, integrity))
+}
+
+/// Add the hash to the file name, before the extension: `custom.css` -> `custom.3f2a9c1e.css`.
+fn fingerprinted_path(path: &Path, hash: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let name = match path.extension() {
+        Some(ext) => format!("{}.{}.{}", stem, hash, ext.to_string_lossy()),
+        None => format!("{}.{}", stem, hash),
+    };
+
+    path.with_file_name(name)
+}
+
+fn site_path<S: AsRef<str>>(path: S) -> String {
+    format!("/{}", path.as_ref().trim_start_matches('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprinted_path() {
+        assert_eq!(
+            fingerprinted_path(Path::new("assets/custom.css"), "3f2a9c1e"),
+            PathBuf::from("assets/custom.3f2a9c1e.css")
+        );
+        assert_eq!(
+            fingerprinted_path(Path::new("assets/jquery.min.js"), "3f2a9c1e"),
+            PathBuf::from("assets/jquery.min.3f2a9c1e.js")
+        );
+        assert_eq!(
+            fingerprinted_path(Path::new("assets/LICENSE"), "3f2a9c1e"),
+            PathBuf::from("assets/LICENSE.3f2a9c1e")
+        );
+    }
+
+    #[test]
+    fn test_lookup() {
+        let entry = ManifestEntry {
+            path: "/assets/custom.3f2a9c1e.css".into(),
+            integrity: "sha384-foo".into(),
+        };
+
+        let mut manifest = Manifest::default();
+        manifest.insert("assets/custom.css", entry.clone());
+
+        assert_eq!(manifest.get("/assets/custom.css"), Some(&entry));
+        assert_eq!(manifest.get("assets/custom.css"), Some(&entry));
+        assert_eq!(manifest.get("/assets/other.css"), None);
+    }
+}
@@ -1,40 +1,66 @@
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use handlebars::{Handlebars, HelperDef};
 
-use log::{debug, info};
+use log::{debug, info, warn};
 
+use crate::diagnostic::Diagnostic;
+use crate::engine::handlebars::HandlebarsEngine;
+use crate::engine::tera::TeraEngine;
+use crate::engine::{Engine, TemplateEngine};
 use crate::error::GeneratorError;
+use crate::ignores::Ignores;
+use crate::loader::cascade::CascadeRules;
 use crate::loader::directory::DirectoryLoader;
-use crate::loader::Loader;
-use crate::rules::{Asset, Render, Rule};
+use crate::loader::language::LanguageFilter;
+use crate::loader::schema::SchemaValidator;
+use crate::loader::{Loader, Resource};
+use crate::rules::{
+    Asset, Collisions, Conflicts, Directories, IgnoreConfig, ImageOptions, Language, MinifyOptions,
+    Render, Rule,
+};
+use crate::theme::Theme;
 
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 
 use std::fs::File;
+use std::io::Write;
 
 type Result<T> = std::result::Result<T, GeneratorError>;
 
+use crate::helper::asset::{AssetIntegrityHelper, AssetUrlHelper};
 use crate::helper::basic::{ConcatHelper, DumpHelper, ExpandHelper, TimesHelper};
+use crate::helper::i18n::TranslateHelper;
+use crate::helper::images::ImageHelper;
 use crate::helper::markdown::MarkdownifyHelper;
 
+use crate::clean;
+use crate::compress;
 use crate::copy;
 use crate::helper::time::TimeHelper;
 use crate::helper::url::{full_url_for, AbsoluteUrlHelper, ActiveHelper, RelativeUrlHelper};
 use relative_path::RelativePath;
 
+use crate::fingerprint::{self, Manifest, ManifestEntry, MANIFEST_FILE};
+use crate::i18n::Catalogs;
+use crate::minify;
 use crate::processor::{Processor, ProcessorSession};
+use crate::redirect::{write_redirects_file, Redirect, REDIRECTS_FILE};
+use crate::sass;
 
 use crate::helper::sort::SortedHelper;
+use crate::processor::linkcheck::LinkCheckProcessor;
 use crate::processor::rss::RssProcessor;
+use crate::processor::search::SearchProcessor;
 use crate::processor::sitemap::SitemapProcessor;
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex, RwLock};
 
+use chrono::{DateTime, Utc};
 use std::str::FromStr;
 use url::Url;
 
@@ -42,6 +68,37 @@ lazy_static! {
     static ref RE: Regex = Regex::new(r"/{2,}").unwrap();
 }
 
+/// The output directory, relative to the root, unless configured otherwise.
+const DEFAULT_OUTPUT: &str = "output";
+
+/// A file produced by the build, for detecting conflicts.
+struct Produced {
+    source: String,
+    asset: bool,
+}
+
+/// The content of a single language of the site.
+struct ContentTree {
+    /// The language, `None` if the site isn't multilingual.
+    language: Option<Language>,
+    /// The directory the content was loaded from.
+    dir: PathBuf,
+    full: Value,
+    compact: Value,
+}
+
+/// A translation of a page into another language.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Translation {
+    /// The language code.
+    pub language: String,
+    /// The display name of the language.
+    pub name: Option<String>,
+    /// The URL of the translated page.
+    pub url: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Output {
@@ -53,6 +110,12 @@ pub struct Output {
     pub template: Option<String>,
     // the output URL
     pub url: String,
+    // the language of the page, on multilingual sites
+    #[serde(default)]
+    pub language: Option<String>,
+    // the translations of the page into the other languages
+    #[serde(default)]
+    pub translations: Vec<Translation>,
 }
 
 impl Output {
@@ -83,6 +146,8 @@ impl Output {
             url: url.into_string(),
             site_url: site_url_str,
             template: template.map(|s| s.into()),
+            language: None,
+            translations: Vec::new(),
         })
     }
 }
@@ -92,6 +157,42 @@ pub struct GeneratorConfig {
     pub root: PathBuf,
     pub output: PathBuf,
     pub basename: Url,
+    /// The manifest of fingerprinted assets.
+    pub manifest: Arc<Manifest>,
+    /// The options of the image pipeline, `None` if images must not be processed (dry run).
+    pub images: Option<ImageOptions>,
+    pub minify: MinifyOptions,
+    /// The languages of a multilingual site, the first one is the default language.
+    pub languages: Vec<Language>,
+    /// The string catalogs of the `t` helper.
+    pub catalogs: Arc<Catalogs>,
+    /// The build timestamp, used for relative timestamps.
+    pub build_time: DateTime<Utc>,
+    /// The directories files of the output get copied from, as pairs of an output path prefix
+    /// (like `assets/`) and the source directory.
+    pub sources: Vec<(String, PathBuf)>,
+    /// The files produced by helpers while rendering (image variants), relative to the output.
+    pub produced: Arc<Mutex<Vec<String>>>,
+}
+
+impl GeneratorConfig {
+    /// A configuration with default values, for tests which only care about the basename.
+    #[cfg(test)]
+    pub(crate) fn for_test(basename: &str) -> Self {
+        GeneratorConfig {
+            root: "/tmp".into(),
+            output: "/tmp/output".into(),
+            basename: Url::parse(basename).expect("Valid basename"),
+            manifest: Default::default(),
+            images: Default::default(),
+            minify: Default::default(),
+            languages: Default::default(),
+            catalogs: Default::default(),
+            build_time: Utc::now(),
+            sources: Default::default(),
+            produced: Default::default(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -118,6 +219,9 @@ pub struct GeneratorBuilder<'a> {
 
     root: PathBuf,
     basename_override: Option<String>,
+    output_override: Option<PathBuf>,
+    clean: bool,
+    keep_going: bool,
     dump: bool,
 }
 
@@ -145,6 +249,19 @@ impl GeneratorContextProvider {
 
         func(context)
     }
+
+    /// Like `with`, but also usable when no page is being rendered, like for output patterns.
+    pub fn with_optional<F, T>(&self, func: F) -> Result<T>
+    where
+        F: FnOnce(Option<&GeneratorContext>) -> Result<T>,
+    {
+        let context = self
+            .provider
+            .read()
+            .map_err(|_| GeneratorError::Error("Failed to get generator context".into()))?;
+
+        func(context.as_ref())
+    }
 }
 
 impl Clone for GeneratorContextProvider {
@@ -166,6 +283,9 @@ impl<'a> GeneratorBuilder<'a> {
 
             root: root.into(),
             basename_override: None,
+            output_override: None,
+            clean: true,
+            keep_going: false,
             dump: false,
         };
     }
@@ -187,7 +307,29 @@ impl<'a> GeneratorBuilder<'a> {
         self
     }
 
-    /// Register an additional helper.
+    /// Override the output directory, read from the configuration.
+    pub fn override_output<P: Into<PathBuf>>(mut self, output: Option<P>) -> Self {
+        self.output_override = output.map(|p| p.into());
+        self
+    }
+
+    /// Should the whole output directory be cleaned before building? Defaults to: `true`.
+    ///
+    /// If disabled, only the files produced by the previous build will be removed.
+    pub fn clean(mut self, clean: bool) -> Self {
+        self.clean = clean;
+        self
+    }
+
+    /// Should the build continue when rendering an entry fails? Defaults to: `false`.
+    ///
+    /// If enabled, all errors will be collected, and the build fails at the end.
+    pub fn keep_going(mut self, keep_going: bool) -> Self {
+        self.keep_going = keep_going;
+        self
+    }
+
+    /// Register an additional helper. Only used by the Handlebars template engine.
     pub fn register_helper<S: Into<String>>(
         mut self,
         name: S,
@@ -219,6 +361,8 @@ impl<'a> GeneratorBuilder<'a> {
         if self.default_processors {
             processors.insert("sitemap".into(), Box::new(SitemapProcessor));
             processors.insert("rss".into(), Box::new(RssProcessor));
+            processors.insert("search".into(), Box::new(SearchProcessor));
+            processors.insert("linkcheck".into(), Box::new(LinkCheckProcessor));
         }
 
         for (name, processor) in self.processors {
@@ -238,7 +382,12 @@ impl<'a> GeneratorBuilder<'a> {
 
             handlebars.register_helper("markdownify", Box::new(MarkdownifyHelper));
 
-            handlebars.register_helper("timestamp", Box::new(TimeHelper));
+            handlebars.register_helper(
+                "timestamp",
+                Box::new(TimeHelper {
+                    context: context_provider.clone(),
+                }),
+            );
 
             handlebars.register_helper(
                 "absolute_url",
@@ -252,6 +401,30 @@ impl<'a> GeneratorBuilder<'a> {
                     context: context_provider.clone(),
                 }),
             );
+            handlebars.register_helper(
+                "asset_url",
+                Box::new(AssetUrlHelper {
+                    context: context_provider.clone(),
+                }),
+            );
+            handlebars.register_helper(
+                "asset_integrity",
+                Box::new(AssetIntegrityHelper {
+                    context: context_provider.clone(),
+                }),
+            );
+            handlebars.register_helper(
+                "image",
+                Box::new(ImageHelper {
+                    context: context_provider.clone(),
+                }),
+            );
+            handlebars.register_helper(
+                "t",
+                Box::new(TranslateHelper {
+                    context: context_provider.clone(),
+                }),
+            );
             handlebars.register_helper(
                 "active",
                 Box::new(ActiveHelper {
@@ -269,98 +442,628 @@ impl<'a> GeneratorBuilder<'a> {
         Generator {
             root,
             basename_override: self.basename_override,
+            output_override: self.output_override,
+            clean: self.clean,
+            keep_going: self.keep_going,
+            dry_run: false,
             dump: self.dump,
+            default_helpers: self.default_helpers,
 
-            handlebars,
+            engines: Engines {
+                active: None,
+                handlebars: HandlebarsEngine::new(handlebars),
+                tera: None,
+            },
 
             processors,
 
             config: Default::default(),
-            full_content: Default::default(),
-            compact_content: Default::default(),
+            trees: Default::default(),
+            current: 0,
+            data_content: Default::default(),
+            redirects: Default::default(),
+            errors: Default::default(),
+            produced: Default::default(),
+            manifest: Default::default(),
+            catalogs: Default::default(),
             context_provider: provider.clone(),
         }
     }
 }
 
+/// The template engines of a generator, only one of them is active for a run.
+struct Engines<'a> {
+    active: Option<Engine>,
+    handlebars: HandlebarsEngine<'a>,
+    tera: Option<TeraEngine>,
+}
+
+impl<'a> Engines<'a> {
+    fn active(&mut self) -> Result<&mut (dyn TemplateEngine + 'a)> {
+        match (self.active, &mut self.tera) {
+            (Some(Engine::Handlebars), _) => Ok(&mut self.handlebars),
+            (Some(Engine::Tera), Some(tera)) => Ok(tera),
+            _ => Err(GeneratorError::Error("Missing template engine".into())),
+        }
+    }
+}
+
 pub struct Generator<'a> {
     root: PathBuf,
     basename_override: Option<String>,
+    output_override: Option<PathBuf>,
+    clean: bool,
+    keep_going: bool,
+    dry_run: bool,
     dump: bool,
+    default_helpers: bool,
 
-    handlebars: Handlebars<'a>,
+    engines: Engines<'a>,
 
     processors: HashMap<String, Box<dyn Processor + 'a>>,
 
     config: Option<Render>,
-    full_content: Value,
-    compact_content: Value,
+    /// The content, one tree per language.
+    trees: Vec<ContentTree>,
+    /// The index of the tree currently being rendered.
+    current: usize,
+    data_content: Value,
+    redirects: Vec<Redirect>,
+    errors: Vec<failure::Error>,
+    produced: HashMap<String, Produced>,
+    manifest: Manifest,
+    catalogs: Catalogs,
 
     context_provider: Arc<RwLock<Option<GeneratorContext>>>,
 }
 
 impl<'a> Generator<'a> {
     fn output(&self) -> PathBuf {
-        self.root.join("output")
+        let configured = self.config.as_ref().and_then(|c| c.output.as_ref());
+        match (&self.output_override, configured) {
+            (Some(output), _) => self.root.join(output),
+            (None, Some(output)) => self.root.join(output),
+            (None, None) => self.root.join(DEFAULT_OUTPUT),
+        }
+    }
+
+    /// The errors collected by the last run, when running with `keep_going`.
+    pub fn errors(&self) -> &[failure::Error] {
+        &self.errors
     }
 
     pub fn run(&mut self) -> Result<()> {
         debug!("Running generator");
 
-        let templates = self.root.join("templates");
-        info!("Loading templates: {:?}", templates);
-        self.handlebars
-            .register_templates_directory(".hbs", templates)?;
+        self.errors.clear();
+        self.produced.clear();
+        self.redirects.clear();
+        self.manifest = Default::default();
+
+        // load config
+        self.load_config()?;
+
+        // create template engine
+        self.create_engine()?;
+
+        // load templates
+        self.load_templates()?;
 
         // clean output
         self.clean()?;
+        let existing = clean::snapshot(self.output())?;
+
+        // load data and build
+        let result = self.load_content().and_then(|_| self.build());
+
+        // record produced files, also when the build failed
+        let mut produced: BTreeSet<_> = clean::snapshot(self.output())?
+            .difference(&existing)
+            .cloned()
+            .collect();
+        if result.is_err() || !self.errors.is_empty() {
+            // keep the files of the previous build, until a successful build replaces them
+            produced.extend(clean::read_marker(self.output())?);
+        }
+        clean::write_marker(self.output(), &produced)?;
 
-        // load data
-        self.load_content()?;
+        result?;
+
+        if !self.errors.is_empty() {
+            return Err(GeneratorError::Error(format!(
+                "Build failed with {} error(s)",
+                self.errors.len()
+            )));
+        }
+
+        // done
+        Ok(())
+    }
+
+    /// Validate configuration, content and templates.
+    ///
+    /// All pages get rendered, but nothing will be written to the output directory. All
+    /// problems are collected, and can be retrieved using `errors()`.
+    pub fn check(&mut self) -> Result<()> {
+        debug!("Checking site");
+
+        let keep_going = self.keep_going;
+        let dry_run = self.dry_run;
+        self.keep_going = true;
+        self.dry_run = true;
+
+        let result = self.check_site();
+
+        self.keep_going = keep_going;
+        self.dry_run = dry_run;
+
+        result
+    }
+
+    fn check_site(&mut self) -> Result<()> {
+        self.errors.clear();
+        self.produced.clear();
+        self.redirects.clear();
 
-        // load config
         self.load_config()?;
+        self.create_engine()?;
+        self.load_templates()?;
+        self.load_content()?;
 
-        // build
-        self.build()?;
+        let config = self
+            .config
+            .as_ref()
+            .ok_or(GeneratorError::Error("Missing site configuration".into()))?
+            .clone();
 
-        // done
+        let generator_config = self.generator_config(&config)?;
+
+        // render into nothing, without running any processor
+
+        let data = self.data(None, None);
+        let mut processors = ProcessorSession::new(
+            &self.processors,
+            self.engines.active()?,
+            &data,
+            &generator_config,
+            &Map::new(),
+        )?;
+
+        info!("Checking rules");
+        for rule in &config.rules {
+            let result = self.check_rule(rule);
+            if self.collect(result)?.is_some() {
+                let result = self.render_rule(rule, &mut processors, &generator_config);
+                self.collect(result)?;
+            }
+        }
+
+        info!("Checking redirects");
+        for (from, to) in &config.redirects {
+            let result = Redirect::new(generator_config.basename.as_str(), from, to);
+            self.collect(result.map_err(GeneratorError::from))?;
+        }
+
+        info!("Checking assets");
+        for a in &config.assets {
+            let dir = self.root.join(&a.dir);
+            if !dir.is_dir() {
+                let result: Result<()> = Err(GeneratorError::Error(format!(
+                    "Missing asset directory: {:?}",
+                    dir
+                )));
+                self.collect(result)?;
+            }
+        }
+
+        if !self.errors.is_empty() {
+            return Err(GeneratorError::Error(format!(
+                "Check failed with {} error(s)",
+                self.errors.len()
+            )));
+        }
+
+        info!("No problems found");
         Ok(())
     }
 
+    /// Validate a rule, and check that a static template name refers to an existing template.
+    fn check_rule(&mut self, rule: &Rule) -> Result<()> {
+        let mut result = rule.validate();
+
+        // templates with expressions can only be checked while rendering
+        if let (Ok(_), Some(ref t)) = (&result, &rule.template) {
+            if !t.contains("{{") && !self.engine()?.has_template(t) {
+                result = Err(GeneratorError::Error(format!("Unknown template: {}", t)).into());
+            }
+        }
+
+        result.map_err(|err| {
+            GeneratorError::GenericDetailError(err, format!("Invalid rule '{}'", rule.describe()))
+        })
+    }
+
+    /// Record an output file, detecting files which are produced more than once.
+    fn record_output(&mut self, path: &str, source: String, asset: bool) -> Result<()> {
+        let previous = self
+            .produced
+            .insert(path.into(), Produced { source, asset });
+
+        let (previous, current) = match (previous, self.produced.get(path)) {
+            (Some(previous), Some(current)) => (previous, current),
+            _ => return Ok(()),
+        };
+
+        if previous.asset && current.asset {
+            debug!(
+                "Asset '{}' of {} overrides {}",
+                path, current.source, previous.source
+            );
+            return Ok(());
+        }
+
+        let message = format!(
+            "Output conflict: '{}' is produced by {} and {}",
+            path, previous.source, current.source
+        );
+
+        match self
+            .config
+            .as_ref()
+            .map(|c| c.conflicts)
+            .unwrap_or_default()
+        {
+            Conflicts::Fail => Err(GeneratorError::Error(message)),
+            Conflicts::Warn => {
+                warn!("{}", message);
+                Ok(())
+            }
+        }
+    }
+
+    /// Record the image variants, which were created while rendering an entry.
+    fn record_images(&mut self, config: &GeneratorConfig, entry: &str) -> Result<()> {
+        let files: Vec<String> = match config.produced.lock() {
+            Ok(mut produced) => produced.drain(..).collect(),
+            Err(_) => return Err(GeneratorError::Error("Failed to get produced files".into())),
+        };
+
+        for file in files {
+            self.record_output(&file, format!("image variant of '{}'", entry), true)?;
+        }
+
+        Ok(())
+    }
+
+    /// Copy the resources of a page bundle into the output directory of the rendered page.
+    fn copy_resources(&mut self, context: &Value, path: &str) -> Result<()> {
+        let resources: Vec<Resource> = match context.get("resources") {
+            Some(resources) => serde_json::from_value(resources.clone())?,
+            None => return Ok(()),
+        };
+
+        if resources.is_empty() {
+            return Ok(());
+        }
+
+        // the resources of untranslated entries are found in the default content tree
+        let tree = if is_fallback(context) {
+            0
+        } else {
+            self.current
+        };
+        let content_dir = self.trees[tree].dir.clone();
+        let dir = RelativePath::new(path)
+            .parent()
+            .unwrap_or_else(|| RelativePath::new(""));
+
+        for resource in resources {
+            let target = dir.join(&resource.name);
+            self.record_output(
+                target.as_str(),
+                format!("resource of '{}'", content_path(context)),
+                true,
+            )?;
+
+            if self.dry_run {
+                continue;
+            }
+
+            let source = RelativePath::new(&resource.path).to_path(&content_dir);
+            let target = target.to_path(self.output());
+            debug!("Copy resource: {:?} -> {:?}", source, target);
+
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(source, target)?;
+        }
+
+        Ok(())
+    }
+
+    /// Either fail, or record the error and continue when running with `keep_going`.
+    fn collect<T>(&mut self, result: Result<T>) -> Result<Option<T>> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(err) if self.keep_going => {
+                // reported by the caller, once the run is complete
+                let err: failure::Error = err.into();
+                debug!("Collected error: {}", err);
+                self.errors.push(err);
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
     fn load_config(&mut self) -> Result<()> {
         let path = self.root.join("hagen.yaml");
         info!("Loading configuration: {:?}", path);
-        self.config = Some(Render::load_from(path)?);
+        let mut config = Render::load_from(path)?;
+
+        if let Some(theme) = config.theme.clone() {
+            info!("Using theme: {}", theme);
+            Theme::load_from(self.root.join(&theme))?.apply(&theme, &mut config);
+        }
+
+        self.config = Some(config);
+
+        Ok(())
+    }
+
+    fn directories(&self) -> Result<&Directories> {
+        Ok(&self
+            .config
+            .as_ref()
+            .ok_or_else(|| GeneratorError::Error("Missing site configuration".into()))?
+            .directories)
+    }
+
+    fn ignore_config(&self) -> IgnoreConfig {
+        self.config
+            .as_ref()
+            .map(|c| c.ignore.clone())
+            .unwrap_or_default()
+    }
+
+    fn create_engine(&mut self) -> Result<()> {
+        let engine = self.config.as_ref().map(|c| c.engine).unwrap_or_default();
+
+        info!("Using template engine: {:?}", engine);
+
+        // start each run without the templates of a previous one
+
+        match engine {
+            Engine::Handlebars => self.engines.handlebars.clear_templates(),
+            Engine::Tera => {
+                self.engines.tera = Some(TeraEngine::new(
+                    GeneratorContextProvider::new(&self.context_provider),
+                    self.default_helpers,
+                ))
+            }
+        }
+
+        self.engines.active = Some(engine);
+
+        Ok(())
+    }
+
+    fn engine(&mut self) -> Result<&mut (dyn TemplateEngine + 'a)> {
+        self.engines.active()
+    }
+
+    fn load_templates(&mut self) -> Result<()> {
+        let templates: Vec<PathBuf> = self
+            .directories()?
+            .templates
+            .iter()
+            .map(|t| self.root.join(t))
+            .collect();
+
+        // later directories override templates of earlier ones
+
+        for t in templates {
+            if !t.is_dir() {
+                debug!("Skipping missing template directory: {:?}", t);
+                continue;
+            }
+            info!("Loading templates: {:?}", t);
+            self.engine()?.register_templates_directory(&t)?;
+        }
 
         Ok(())
     }
 
     fn load_content(&mut self) -> Result<()> {
-        let content = self.root.join("content");
+        let content_dir = self.root.join(&self.directories()?.content);
+        let languages = self
+            .config
+            .as_ref()
+            .map(|c| c.languages.clone())
+            .unwrap_or_default();
 
-        info!("Loading content: {:?}", content);
+        self.trees.clear();
+        self.current = 0;
 
-        // load content
-        let content = DirectoryLoader::new(&content, &content).load_from()?;
+        if languages.is_empty() {
+            let tree = self.load_tree(&content_dir, None, None)?;
+            self.trees.push(tree);
+        }
 
-        // convert to value
-        self.full_content = content.to_value()?;
-        self.compact_content = Generator::compact_content(&self.full_content).unwrap_or_default();
+        // one content tree per language, the first language is the default one
+
+        let codes: Vec<String> = languages.iter().map(|l| l.code.clone()).collect();
+        for (i, language) in languages.into_iter().enumerate() {
+            let dir = match language.content {
+                Some(ref dir) => self.root.join(dir),
+                None => content_dir.clone(),
+            };
+            let filter = LanguageFilter::new(
+                language.code.as_str(),
+                codes.clone(),
+                i == 0 || language.content.is_some(),
+            );
+            let mut tree = self.load_tree(&dir, Some(language), Some(filter))?;
+
+            // fall back to the default language, for entries which are not translated
+            if let Some(default) = self.trees.first() {
+                merge_fallback(&mut tree.full, &default.full);
+                tree.compact = Generator::compact_content(&tree.full).unwrap_or_default();
+            }
+
+            self.trees.push(tree);
+        }
+
+        // load data
+
+        let data = self.root.join(&self.directories()?.data);
+        if data.is_dir() {
+            info!("Loading data: {:?}", data);
+            let ignores = Ignores::new(&data, &self.ignore_config())?;
+            let data = DirectoryLoader::new(&data, &data)
+                .with_ignores(ignores)
+                .with_collisions(self.collisions())
+                .load_from()?;
+            self.data_content = Generator::compact_content(&data.to_value()?).unwrap_or_default();
+        }
+
+        // load translations
 
-        if self.dump {
+        let i18n = self.root.join(&self.directories()?.i18n);
+        self.catalogs = if i18n.is_dir() {
+            Catalogs::load(&i18n)?
+        } else {
+            Default::default()
+        };
+        let language = self.config.as_ref().and_then(|c| c.site.language.clone());
+        self.catalogs.set_default(language);
+
+        if self.dump && !self.dry_run {
             // dump content
             info!("Dumping content");
-            let writer = File::create(self.output().join("content.yaml"))?;
-            serde_yaml::to_writer(writer, &self.full_content)?;
-            let writer = File::create(self.output().join("compact.yaml"))?;
-            serde_yaml::to_writer(writer, &self.compact_content)?;
+            self.record_output("content.yaml", "the content dump".into(), false)?;
+            self.record_output("compact.yaml", "the content dump".into(), false)?;
+            if let Some(tree) = self.trees.first() {
+                let writer = File::create(self.output().join("content.yaml"))?;
+                serde_yaml::to_writer(writer, &tree.full)?;
+                let writer = File::create(self.output().join("compact.yaml"))?;
+                serde_yaml::to_writer(writer, &tree.compact)?;
+            }
         }
 
         // done
         Ok(())
     }
 
+    /// Load the content tree of a single language.
+    fn load_tree(
+        &mut self,
+        content_dir: &Path,
+        language: Option<Language>,
+        filter: Option<LanguageFilter>,
+    ) -> Result<ContentTree> {
+        match language {
+            Some(ref language) => info!("Loading content ({}): {:?}", language.code, content_dir),
+            None => info!("Loading content: {:?}", content_dir),
+        }
+
+        // load content
+        let ignores = Ignores::new(content_dir, &self.ignore_config())?;
+        let content = DirectoryLoader::new(content_dir, content_dir)
+            .with_ignores(ignores)
+            .with_collisions(self.collisions())
+            .with_language(filter)
+            .load_from()?;
+
+        // convert to value
+        let mut full = content.to_value()?;
+
+        // apply cascade rules
+        if let Some(config) = self.config.as_ref() {
+            CascadeRules::new(&config.cascade)?.apply(&mut full);
+        }
+
+        let compact = Generator::compact_content(&full).unwrap_or_default();
+
+        // validate front matter
+        self.validate_content(content_dir, &full)?;
+
+        Ok(ContentTree {
+            language,
+            dir: content_dir.to_path_buf(),
+            full,
+            compact,
+        })
+    }
+
+    /// The source directories of the output, from the assets and the content trees.
+    fn sources(&self, config: &Render) -> Vec<(String, PathBuf)> {
+        let prefix = |path: &str| match normalize_path(path).trim_end_matches('/') {
+            "" => String::new(),
+            path => format!("{}/", path),
+        };
+
+        let assets = config.assets.iter().map(|a| {
+            (
+                prefix(a.to.as_deref().unwrap_or_default()),
+                self.root.join(&a.dir),
+            )
+        });
+
+        // untranslated entries fall back to the directory of the default language
+        let default = self.trees.first().map(|t| t.dir.clone());
+        let content = self.trees.iter().enumerate().flat_map(|(i, tree)| {
+            let code = match tree.language {
+                Some(ref language) if i > 0 => language.code.as_str(),
+                _ => "",
+            };
+            let fallback = default.clone().filter(|dir| *dir != tree.dir);
+            std::iter::once(tree.dir.clone())
+                .chain(fallback)
+                .map(move |dir| (prefix(code), dir))
+        });
+
+        assets.chain(content).collect()
+    }
+
+    fn collisions(&self) -> Collisions {
+        self.config
+            .as_ref()
+            .map(|c| c.collisions)
+            .unwrap_or_default()
+    }
+
+    /// The full content tree of the language currently being rendered.
+    fn full_content(&self) -> &Value {
+        self.trees
+            .get(self.current)
+            .map(|t| &t.full)
+            .unwrap_or(&Value::Null)
+    }
+
+    /// The language currently being rendered.
+    fn language(&self) -> Option<&Language> {
+        self.trees
+            .get(self.current)
+            .and_then(|t| t.language.as_ref())
+    }
+
+    fn validate_content(&mut self, content_dir: &Path, content: &Value) -> Result<()> {
+        let schemas = match self.config.as_ref() {
+            Some(config) => config.schemas.clone(),
+            None => return Ok(()),
+        };
+
+        let validator = SchemaValidator::load(&self.root, &schemas)?;
+
+        for err in validator.validate(content_dir, content) {
+            self.collect::<()>(Err(err))?;
+        }
+
+        Ok(())
+    }
+
     // Compact the content tree to contain only "content" sections.
     fn compact_content(v: &Value) -> Option<Value> {
         match v {
@@ -381,13 +1084,7 @@ impl<'a> Generator<'a> {
         }
     }
 
-    fn build(&mut self) -> Result<()> {
-        let config = self
-            .config
-            .as_ref()
-            .ok_or(GeneratorError::Error("Missing site configuration".into()))?
-            .clone();
-
+    fn generator_config(&self, config: &Render) -> Result<GeneratorConfig> {
         let mut basename = self
             .basename_override
             .as_ref()
@@ -400,45 +1097,140 @@ impl<'a> Generator<'a> {
 
         let basename = Url::from_str(&basename)?;
 
-        // context
-        let generator_config = GeneratorConfig {
-            basename: basename.clone(),
+        let build_time = match config.build_time {
+            Some(ref t) => DateTime::parse_from_rfc3339(t)
+                .map_err(|err| GeneratorError::Error(format!("Invalid 'buildTime': {}", err)))?
+                .with_timezone(&Utc),
+            None => Utc::now(),
+        };
+
+        Ok(GeneratorConfig {
+            basename,
             root: self.root.clone(),
             output: self.output(),
-        };
+            manifest: Arc::new(self.manifest.clone()),
+            images: if self.dry_run {
+                None
+            } else {
+                Some(config.images.clone())
+            },
+            minify: config.minify,
+            languages: config.languages.clone(),
+            catalogs: Arc::new(self.catalogs.clone()),
+            build_time,
+            sources: self.sources(config),
+            produced: Default::default(),
+        })
+    }
+
+    fn build(&mut self) -> Result<()> {
+        let config = self
+            .config
+            .as_ref()
+            .ok_or(GeneratorError::Error("Missing site configuration".into()))?
+            .clone();
+
+        // process assets, before rendering, so that the asset manifest is available
+        info!("Processing assets");
+        for a in &config.assets {
+            let result = self.process_asset(a);
+            self.collect(result)?;
+        }
+
+        if !self.manifest.is_empty() {
+            self.record_output(MANIFEST_FILE, "the asset manifest".into(), false)?;
+            self.manifest.write(self.output())?;
+        }
+
+        // context
+        let generator_config = self.generator_config(&config)?;
 
         let data = self.data(None, None);
         let mut processors = ProcessorSession::new(
             &self.processors,
-            &mut self.handlebars,
+            self.engines.active()?,
             &data,
             &generator_config,
             &config.processors,
         )?;
 
+        for (name, output) in processors.outputs() {
+            let output = normalize_path(output);
+            self.record_output(&output, format!("the '{}' processor", name), false)?;
+        }
+
         // render all rules
         info!("Rendering content");
         for rule in &config.rules {
-            self.render_rule(&rule, &mut processors, &generator_config)?;
+            let result = self.render_rule(&rule, &mut processors, &generator_config);
+            self.collect(result)?;
         }
 
-        // process assets
-        info!("Processing assets");
-        for a in &config.assets {
-            self.process_asset(a)?;
+        // write redirects
+        let result = self.process_redirects(&config, &generator_config);
+        self.collect(result)?;
+
+        let result = processors.complete(self.engine()?);
+        self.collect(result.map_err(GeneratorError::from))?;
+
+        // compress, once all files are written
+        if let Some(ref options) = config.compress {
+            let result = compress::compress_dir(self.output(), options);
+            if let Some(written) = self.collect(result.map_err(GeneratorError::from))? {
+                for file in written {
+                    let path = normalize_path(file.to_string_lossy());
+                    let result = self.record_output(&path, "compression".into(), false);
+                    self.collect(result)?;
+                }
+            }
         }
 
-        processors.complete(&mut self.handlebars)?;
-
         info!("Done");
         // done
         Ok(())
     }
 
-    fn process_asset(&self, asset: &Asset) -> Result<()> {
+    fn process_redirects(
+        &mut self,
+        config: &Render,
+        generator_config: &GeneratorConfig,
+    ) -> Result<()> {
+        for (from, to) in &config.redirects {
+            self.redirects
+                .push(Redirect::new(generator_config.basename.as_str(), from, to)?);
+        }
+
+        if self.redirects.is_empty() {
+            return Ok(());
+        }
+
+        let pages: Vec<(String, String)> = self
+            .redirects
+            .iter()
+            .map(|r| (r.from.path.clone(), format!("redirect to '{}'", r.to)))
+            .collect();
+        for (path, source) in pages {
+            self.record_output(&path, source, false)?;
+        }
+
+        info!("Writing {} redirects", self.redirects.len());
+
+        for r in &self.redirects {
+            r.write_page(&generator_config.output)?;
+        }
+
+        if config.site.redirects_file {
+            self.record_output(REDIRECTS_FILE, "the redirects file".into(), false)?;
+            write_redirects_file(&generator_config.output, &self.redirects)?;
+        }
+
+        Ok(())
+    }
+
+    fn process_asset(&mut self, asset: &Asset) -> Result<()> {
         let from = self.root.join(&asset.dir);
 
-        let mut target = self.root.join("output");
+        let mut target = self.output();
         if let Some(ref to) = asset.to {
             target = target.join(to);
         }
@@ -447,7 +1239,62 @@ impl<'a> Generator<'a> {
 
         fs::create_dir_all(&target)?;
 
-        copy::copy_dir(&from, &target, asset.glob.as_ref())?;
+        let ignores = Ignores::new(&from, &self.ignore_config())?.with_hidden(&asset.hidden);
+        let mut copied = match asset.sass {
+            Some(ref options) => sass::compile_dir(
+                &self.root,
+                &from,
+                &target,
+                asset.glob.as_ref(),
+                &ignores,
+                options,
+            )?,
+            None => copy::copy_dir(&from, &target, asset.glob.as_ref(), &ignores)?,
+        };
+
+        // minifying the compiled CSS would invalidate its source map
+        let source_maps = asset.sass.as_ref().is_some_and(|s| s.source_map);
+        if asset.minify && !source_maps {
+            for file in &copied {
+                minify::minify_file(target.join(file))?;
+            }
+        }
+
+        for bundle in &asset.bundles {
+            minify::write_bundle(&from, &target, bundle, asset.minify)?;
+            copied.push(PathBuf::from(&bundle.output));
+        }
+
+        let prefix = asset.to.as_deref().unwrap_or_default();
+
+        if asset.fingerprint {
+            for file in copied.iter_mut() {
+                // source maps are referenced by name from their CSS file
+                if file.extension().is_some_and(|e| e == "map") {
+                    continue;
+                }
+                let (hashed, integrity) =
+                    fingerprint::fingerprint_file(target.join(file.as_path()))?;
+                let hashed = match hashed.file_name() {
+                    Some(name) => file.with_file_name(name),
+                    None => continue,
+                };
+
+                self.manifest.insert(
+                    site_path(prefix, file),
+                    ManifestEntry {
+                        path: site_path(prefix, &hashed),
+                        integrity,
+                    },
+                );
+                *file = hashed;
+            }
+        }
+
+        for file in copied {
+            let path = normalize_path(site_path(prefix, &file));
+            self.record_output(&path, format!("asset '{}'", asset.dir), true)?;
+        }
 
         Ok(())
     }
@@ -463,49 +1310,177 @@ impl<'a> Generator<'a> {
             rule.selector_type, rule.selector, rule.template, rule.output_pattern
         );
 
-        let result: Vec<_> = rule.processor()?.query(&self.full_content)?;
-        let result: Vec<Value> = result.iter().cloned().cloned().collect();
+        // select entries, in all languages
+        let query = rule.processor()?;
+        let mut selected: Vec<Vec<Value>> = Vec::with_capacity(self.trees.len());
+        for tree in &self.trees {
+            let result = query.query(&tree.full)?;
+            selected.push(result.into_iter().cloned().collect());
+        }
 
-        info!("Matches {} entries", result.len());
+        // index the entries by translation key, for finding the translations
+        let keys: Vec<HashMap<String, &Value>> = if selected.len() > 1 {
+            selected
+                .iter()
+                .map(|entries| {
+                    entries
+                        .iter()
+                        .filter_map(|e| translation_key(e).map(|k| (k, e)))
+                        .collect()
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
 
-        // process selected entries
-        for entry in &result {
-            debug!("Processing entry: {}", entry);
-            self.process_render(rule, entry, processors, config)?;
+        for (index, entries) in selected.iter().enumerate() {
+            self.current = index;
+            info!("Matches {} entries", entries.len());
+
+            // process selected entries
+            for entry in entries {
+                debug!("Processing entry: {}", entry);
+                let result = self
+                    .translations(rule, index, entry, &keys, config)
+                    .and_then(|translations| {
+                        self.process_render(rule, entry, translations, processors, config)
+                    })
+                    .map_err(|err| self.render_error(rule, entry, err));
+                let images = self.record_images(config, &content_path(entry));
+                self.collect(result.and(images))?;
+            }
         }
 
+        self.current = 0;
+
         // done
         Ok(())
     }
 
+    /// Evaluate the output path of an entry, prefixed with the language code of the tree.
+    ///
+    /// Pages of the default language are rendered without a prefix.
+    fn output_path(&mut self, rule: &Rule, entry: &Value, index: usize) -> Result<String> {
+        let path = self
+            .engine()?
+            .render_template(&rule.output_pattern, entry)?;
+
+        Ok(match self.trees[index].language {
+            Some(ref language) if index > 0 => {
+                normalize_path(format!("{}/{}", language.code, path))
+            }
+            _ => normalize_path(path),
+        })
+    }
+
+    /// Find the translations of an entry, selected by the same rule in the other languages.
+    fn translations(
+        &mut self,
+        rule: &Rule,
+        index: usize,
+        entry: &Value,
+        keys: &[HashMap<String, &Value>],
+        config: &GeneratorConfig,
+    ) -> Result<Vec<Translation>> {
+        // untranslated entries have no translations, and are no translation themselves
+        let key = match translation_key(entry) {
+            Some(key) if !keys.is_empty() && !is_fallback(entry) => key,
+            _ => return Ok(Vec::new()),
+        };
+
+        let mut result = Vec::new();
+
+        for (i, entries) in keys.iter().enumerate() {
+            let sibling = match entries.get(&key) {
+                Some(sibling) if i != index && !is_fallback(sibling) => *sibling,
+                _ => continue,
+            };
+            let language = match self.trees[i].language {
+                Some(ref language) => language.clone(),
+                None => continue,
+            };
+
+            let path = self.output_path(rule, sibling, i)?;
+            let output = Output::new(config.basename.as_str(), path, None::<String>)?;
+
+            result.push(Translation {
+                language: language.code,
+                name: language.name,
+                url: output.url,
+            });
+        }
+
+        Ok(result)
+    }
+
+    /// Wrap a render error with the context it occurred in.
+    fn render_error(&self, rule: &Rule, entry: &Value, err: GeneratorError) -> GeneratorError {
+        let cause: failure::Error = err.into();
+
+        let template_file = Diagnostic::from_error(&cause)
+            .template
+            .and_then(|t| self.template_file(&t));
+
+        GeneratorError::Render {
+            rule: rule.describe(),
+            content: content_path(entry),
+            template_file,
+            cause,
+        }
+    }
+
+    /// Find the file of a named template, taking overrides into account.
+    fn template_file(&self, name: &str) -> Option<PathBuf> {
+        let directories = self.directories().ok()?;
+
+        directories
+            .templates
+            .iter()
+            .rev()
+            .flat_map(|d| {
+                let dir = self.root.join(d);
+                vec![
+                    dir.join(format!("{}.hbs", name)),
+                    dir.join(format!("{}.tera", name)),
+                ]
+            })
+            .find(|f| f.is_file())
+    }
+
     fn process_render(
         &mut self,
         rule: &Rule,
         context: &Value,
+        translations: Vec<Translation>,
         processors: &mut ProcessorSession,
         config: &GeneratorConfig,
     ) -> Result<()> {
         // eval
-        let path = self
-            .handlebars
-            .render_template(&rule.output_pattern, context)?;
-        let path = normalize_path(path);
-        let template = rule
-            .template
-            .as_ref()
-            .map(|t| self.handlebars.render_template(&t, context))
-            .transpose()?;
+        let path = self.output_path(rule, context, self.current)?;
+
+        self.record_output(
+            &path,
+            format!("rule '{}' ({})", rule.describe(), content_path(context)),
+            false,
+        )?;
+
+        // copy page resources first, so that helpers (like `image`) can process them
+
+        self.copy_resources(context, &path)?;
+
+        let template = match rule.template {
+            Some(ref t) => Some(self.engine()?.render_template(&t, context)?),
+            None => None,
+        };
 
         let relative_target = RelativePath::new(&path);
         let target = relative_target.to_path(self.output());
 
-        if let Some(parent) = target.parent() {
-            fs::create_dir_all(parent)?;
-        }
-
         // page data
 
-        let output = Output::new(config.basename.as_str(), &path, template.as_ref())?;
+        let mut output = Output::new(config.basename.as_str(), &path, template.as_ref())?;
+        output.language = self.language().map(|l| l.code.clone());
+        output.translations = translations;
 
         {
             let ctx = GeneratorContext::new(config, &output);
@@ -517,34 +1492,65 @@ impl<'a> Generator<'a> {
             info!("Render '{}' with '{:?}'", path, template);
             info!("  Target: {:?}", target);
 
-            let writer = File::create(target)?;
+            let minify = config.minify.html && minify::is_html(&path);
+
+            // render into a buffer, so that a failed render doesn't leave a partial file
+            let mut buffer = Vec::new();
+            let writer: &mut dyn Write = &mut buffer;
 
             let context = Generator::build_context(&rule, &context)?;
             let data = &self.data(Some(output_value), Some(context.clone()));
 
             match template {
-                Some(ref t) => self.handlebars.render_to_write(t, data, writer)?,
+                Some(ref t) => self.engine()?.render_to_write(t, data, writer)?,
                 None => {
                     let content = match &context.as_object().and_then(|s| s.get("content")) {
                     Some(Value::String(c)) => Ok(c),
                     _ => Err(GeneratorError::Error("Rule is missing 'template' on rule and '.content' value in context. Either must be set.".into())),
                 }?;
-                    self.handlebars
+                    self.engine()?
                         .render_template_to_write(content, data, writer)?;
                 }
             }
 
+            if !self.dry_run {
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                if minify {
+                    fs::write(&target, minify::minify_html(&buffer))?;
+                } else {
+                    fs::write(&target, &buffer)?;
+                }
+            }
+
             // call processors
-            processors.file_created(&output, data, &mut self.handlebars)?;
+            processors.file_created(&output, data, self.engine()?)?;
 
             // reset current context
             self.context_provider.write().unwrap().take();
         }
 
+        // record aliases
+
+        for alias in Generator::aliases(context) {
+            self.redirects
+                .push(Redirect::new(config.basename.as_str(), alias, &output.url)?);
+        }
+
         // done
         Ok(())
     }
 
+    /// Get the aliases (old paths) of an entry, from the front matter.
+    fn aliases(context: &Value) -> Vec<&str> {
+        match context.pointer("/frontMatter/aliases") {
+            Some(Value::String(alias)) => vec![alias.as_str()],
+            Some(Value::Array(aliases)) => aliases.iter().filter_map(|a| a.as_str()).collect(),
+            _ => vec![],
+        }
+    }
+
     /// Build the render content context object from the rules context mappings
     fn build_context(rule: &Rule, context: &Value) -> Result<Value> {
         if rule.context.is_empty() {
@@ -590,29 +1596,70 @@ impl<'a> Generator<'a> {
             data.insert("context".into(), context);
         }
         // add the full content tree
-        data.insert("full".into(), self.full_content.clone());
+        data.insert("full".into(), self.full_content().clone());
         // add the compact content tree
-        data.insert("compact".into(), self.compact_content.clone());
+        let compact = self.trees.get(self.current).map(|t| t.compact.clone());
+        data.insert("compact".into(), compact.unwrap_or_default());
+        // add the current language
+        if let Some(language) = self.language() {
+            data.insert(
+                "language".into(),
+                serde_json::to_value(language).unwrap_or_default(),
+            );
+        }
+        // add the compact data tree
+        data.insert("data".into(), self.data_content.clone());
 
         // convert to json object
         serde_json::value::Value::Object(data)
     }
 
     pub fn clean(&self) -> Result<()> {
-        let p = self.output();
-        let p = p.as_path();
-
-        if p.exists() {
-            info!("Cleaning up: {:?}", self.output());
-            fs::remove_dir_all(self.output().as_path())?;
+        // earlier versions always built into the default directory, without a marker file
+        if self.output() == self.root.join(DEFAULT_OUTPUT) {
+            clean::adopt(self.output())?;
         }
 
-        fs::create_dir_all(p)?;
+        if self.clean {
+            clean::clean_all(self.output())?;
+        } else {
+            clean::clean_previous(self.output())?;
+        }
 
         Ok(())
     }
 }
 
+/// Get the key identifying the translations of an entry: its path, without the language suffix.
+fn translation_key(entry: &Value) -> Option<String> {
+    let parent = entry.pointer("/metadata/parent")?.as_str()?;
+    let name = entry.pointer("/metadata/name")?.as_str()?;
+
+    Some(format!("{}/{}", parent.trim_end_matches('/'), name))
+}
+
+/// Get the path of the content file of an entry, relative to the content directory.
+fn content_path(entry: &Value) -> String {
+    let parent = entry
+        .pointer("/metadata/parent")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+    let filename = entry
+        .pointer("/metadata/filename")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+
+    format!("{}/{}", parent.trim_end_matches('/'), filename)
+}
+
+/// Get the path of an asset file, relative to the site.
+fn site_path(prefix: &str, file: &Path) -> String {
+    format!(
+        "/{}",
+        normalize_path(format!("{}/{}", prefix, file.to_string_lossy()))
+    )
+}
+
 /// Normalize a path.
 fn normalize_path<S: AsRef<str>>(path: S) -> String {
     // translate backslashes into forward slashes
@@ -624,10 +1671,237 @@ fn normalize_path<S: AsRef<str>>(path: S) -> String {
     s.trim_start_matches('/').into()
 }
 
+/// Check if an entry was taken from the content tree of the default language.
+fn is_fallback(entry: &Value) -> bool {
+    entry.pointer("/metadata/fallback") == Some(&Value::Bool(true))
+}
+
+/// Mark an entry, and all of its children, as taken from the fallback content tree.
+fn mark_fallback(entry: &mut Value) {
+    if let Some(Value::Object(metadata)) = entry.get_mut("metadata") {
+        metadata.insert("fallback".into(), Value::Bool(true));
+    }
+    if entry.pointer("/metadata/type") != Some(&Value::from("directory")) {
+        return;
+    }
+    if let Some(Value::Object(children)) = entry.get_mut("content") {
+        children.values_mut().for_each(mark_fallback);
+    }
+}
+
+/// Add the entries of the fallback content tree, which are missing in the content tree.
+fn merge_fallback(content: &mut Value, fallback: &Value) {
+    let is_dir = |v: &Value| v.pointer("/metadata/type") == Some(&Value::from("directory"));
+    if !is_dir(content) || !is_dir(fallback) {
+        return;
+    }
+
+    if let (Some(Value::Object(children)), Some(Value::Object(fallbacks))) =
+        (content.get_mut("content"), fallback.get("content"))
+    {
+        for (name, fallback) in fallbacks {
+            match children.get_mut(name) {
+                Some(child) => merge_fallback(child, fallback),
+                None => {
+                    let mut fallback = fallback.clone();
+                    mark_fallback(&mut fallback);
+                    children.insert(name.clone(), fallback);
+                }
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Write the files of a site into a temporary directory, returning its root.
+    fn site(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("hagen-site-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+
+        for (path, content) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+
+        root
+    }
+
+    const LAYOUT_RULE: &str = r#"
+site:
+  basename: https://example.com/
+rules:
+  - selectorType: layout
+    template: "{{ frontMatter.layout }}"
+    outputPattern: "{{ metadata.parent }}/{{ metadata.name }}.html"
+    context:
+      page: $
+"#;
+
+    #[test]
+    fn test_keep_going() {
+        let root = site(
+            "keep-going",
+            &[
+                ("hagen.yaml", LAYOUT_RULE),
+                ("content/a.md", "---\nlayout: broken\n---\n"),
+                ("content/b.md", "---\nlayout: page\n---\n"),
+                ("content/c.md", "---\nlayout: broken\n---\n"),
+                ("templates/page.hbs", "ok"),
+                ("templates/broken.hbs", "{{ missing }}"),
+            ],
+        );
+
+        let mut generator = GeneratorBuilder::new(&root).keep_going(true).build();
+        let result = generator.run();
+        let errors: Vec<String> = generator
+            .errors()
+            .iter()
+            .map(|err| err.to_string())
+            .collect();
+        let page = fs::read_to_string(root.join("output/b.html"));
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(result.is_err());
+        assert_eq!(errors.len(), 2, "Unexpected errors: {:?}", errors);
+        assert!(errors[0].contains("/a.md"), "{}", errors[0]);
+        assert!(errors[1].contains("/c.md"), "{}", errors[1]);
+        assert_eq!(page.ok().as_deref(), Some("ok"));
+    }
+
+    #[test]
+    fn test_directories() {
+        let root = site(
+            "directories",
+            &[
+                (
+                    "hagen.yaml",
+                    &LAYOUT_RULE.replace(
+                        "rules:",
+                        "directories: {content: src, data: db, templates: [layouts, overrides]}\nrules:",
+                    ),
+                ),
+                ("src/index.md", "---\nlayout: page\n---\n"),
+                ("db/menu.yaml", "title: Menu"),
+                ("data/menu.yaml", "title: Ignored"),
+                ("layouts/page.hbs", "{{> title }} {{ data.menu.title }}"),
+                ("layouts/title.hbs", "Layout"),
+                ("overrides/title.hbs", "Override"),
+            ],
+        );
+
+        let mut generator = GeneratorBuilder::new(&root).build();
+        let result = generator.run();
+        let page = fs::read_to_string(root.join("output/index.html"));
+        fs::remove_dir_all(&root).unwrap();
+
+        result.unwrap();
+        assert_eq!(page.ok().as_deref(), Some("Override Menu"));
+    }
+
+    fn generator_with(config: &str) -> Generator<'static> {
+        let mut generator = GeneratorBuilder::new("/tmp/site").build();
+        generator.config = Some(Render::load(config.as_bytes()).unwrap());
+        generator
+    }
+
+    #[test]
+    fn test_record_output() {
+        let mut g = generator_with("site: {basename: 'https://example.com/'}");
+
+        assert!(g
+            .record_output("index.html", "rule 'a'".into(), false)
+            .is_ok());
+        // conflicts are reported as warnings by default, the last file wins
+        assert!(g
+            .record_output("index.html", "rule 'b'".into(), false)
+            .is_ok());
+        assert_eq!(g.produced["index.html"].source, "rule 'b'");
+    }
+
+    #[test]
+    fn test_record_output_fail() {
+        let mut g = generator_with("site: {basename: 'https://example.com/'}\nconflicts: fail");
+
+        // assets may override assets, e.g. of a theme
+        assert!(g
+            .record_output("site.css", "asset 'theme'".into(), true)
+            .is_ok());
+        assert!(g
+            .record_output("site.css", "asset 'assets'".into(), true)
+            .is_ok());
+
+        let err = g
+            .record_output("site.css", "rule 'a'".into(), false)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Output conflict: 'site.css' is produced by asset 'assets' and rule 'a'"
+        );
+
+        assert!(g
+            .record_output("_redirects", "asset 'assets'".into(), true)
+            .is_ok());
+        assert!(g
+            .record_output("_redirects", "the redirects file".into(), false)
+            .is_err());
+    }
+
+    #[test]
+    fn test_record_images() {
+        let mut g = generator_with("site: {basename: 'https://example.com/'}\nconflicts: fail");
+        let config = GeneratorConfig::for_test("https://example.com/");
+        config
+            .produced
+            .lock()
+            .unwrap()
+            .extend(vec!["photo.jpg".to_string(), "photo-480w.jpg".to_string()]);
+
+        assert!(g
+            .record_output("photo.jpg", "asset 'assets'".into(), true)
+            .is_ok());
+        assert!(g.record_images(&config, "/index.md").is_ok());
+        assert_eq!(
+            g.produced["photo-480w.jpg"].source,
+            "image variant of '/index.md'"
+        );
+        assert!(config.produced.lock().unwrap().is_empty());
+
+        // a page must not overwrite a variant
+        assert!(g
+            .record_output("photo-480w.jpg", "rule 'a'".into(), false)
+            .is_err());
+    }
+
+    #[test]
+    fn test_compression_conflict() {
+        let script = "console.log('Hello World');\n".repeat(100);
+        let root = site(
+            "compression",
+            &[
+                (
+                    "hagen.yaml",
+                    "site: {basename: 'https://example.com/'}\nconflicts: fail\ncompress: {}\nassets: [{dir: assets}]",
+                ),
+                ("content/index.md", "Hello"),
+                ("assets/app.js", &script),
+                ("assets/app.js.gz", "pre-compressed"),
+            ],
+        );
+
+        let mut generator = GeneratorBuilder::new(&root).build();
+        let result = generator.run();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Output conflict: 'app.js.gz' is produced by asset 'assets' and compression"
+        );
+    }
+
     #[test]
     fn test_1() {
         assert_eq!(normalize_path(""), "");
@@ -657,4 +1931,87 @@ mod tests {
     fn test_double_back_2() {
         assert_eq!(normalize_path("\\\\foo//bar/baz"), "foo/bar/baz");
     }
+
+    #[test]
+    fn test_translation_key() {
+        let entry = serde_json::json!({
+            "metadata": {"parent": "/docs", "name": "intro", "filename": "intro.de.md"}
+        });
+        assert_eq!(translation_key(&entry).as_deref(), Some("/docs/intro"));
+
+        let entry = serde_json::json!({"metadata": {"parent": "/", "name": "index"}});
+        assert_eq!(translation_key(&entry).as_deref(), Some("/index"));
+
+        assert_eq!(translation_key(&serde_json::json!({})), None);
+    }
+
+    #[test]
+    fn test_merge_fallback() {
+        let dir = |content: Value| serde_json::json!({"metadata": {"type": "directory"}, "content": content});
+        let page = |language: &str| serde_json::json!({"metadata": {"type": "md", "language": language}, "content": ""});
+
+        let fallback = dir(serde_json::json!({
+            "index": page("en"),
+            "site": page("en"),
+            "docs": dir(serde_json::json!({ "intro": page("en"), "api": page("en") })),
+        }));
+        let mut content = dir(serde_json::json!({
+            "index": page("de"),
+            "docs": dir(serde_json::json!({ "intro": page("de") })),
+        }));
+
+        merge_fallback(&mut content, &fallback);
+
+        let fallback = |mut entry: Value| {
+            entry["metadata"]["fallback"] = Value::Bool(true);
+            entry
+        };
+        assert_eq!(
+            content,
+            dir(serde_json::json!({
+                "index": page("de"),
+                "site": fallback(page("en")),
+                "docs": dir(serde_json::json!({ "intro": page("de"), "api": fallback(page("en")) })),
+            }))
+        );
+    }
+
+    #[test]
+    fn test_fallback_bundle() {
+        let root = site(
+            "fallback",
+            &[
+                (
+                    "hagen.yaml",
+                    &LAYOUT_RULE.replace("rules:", "languages: [{code: en}, {code: de, content: content-de}]\nrules:"),
+                ),
+                ("content/index.md", "---\nlayout: page\n---\n"),
+                ("content/blog/post/index.md", "---\nlayout: page\n---\n"),
+                ("content/blog/post/image.png", "png"),
+                ("content-de/index.md", "---\nlayout: page\n---\n"),
+                (
+                    "templates/page.hbs",
+                    "{{#each output.translations}}{{ language }}{{/each}}|{{#if context.page.metadata.fallback}}fallback{{/if}}",
+                ),
+            ],
+        );
+
+        let mut generator = GeneratorBuilder::new(&root).build();
+        let result = generator.run();
+        let read = |path: &str| fs::read_to_string(root.join("output").join(path)).ok();
+        let (index, index_de) = (read("index.html"), read("de/index.html"));
+        let (post, post_de) = (
+            read("blog/post/index.html"),
+            read("de/blog/post/index.html"),
+        );
+        let image = read("de/blog/post/image.png");
+        fs::remove_dir_all(&root).unwrap();
+
+        result.unwrap();
+        assert_eq!(index.as_deref(), Some("de|"));
+        assert_eq!(index_de.as_deref(), Some("en|"));
+        assert_eq!(post.as_deref(), Some("|"));
+        assert_eq!(post_de.as_deref(), Some("|fallback"));
+        assert_eq!(image.as_deref(), Some("png"));
+    }
 }
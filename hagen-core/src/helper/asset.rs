@@ -0,0 +1,153 @@
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, RenderError,
+};
+
+use crate::generator::{GeneratorContext, GeneratorContextProvider};
+use crate::helper::url::full_url_from;
+
+/// Resolve a logical asset path through the manifest, into the URL path of the asset.
+///
+/// Assets which are not fingerprinted resolve to their own path.
+pub(crate) fn asset_url(context: &GeneratorContext, path: &str) -> Result<String, RenderError> {
+    let path = match context.config.manifest.get(path) {
+        Some(entry) => entry.path.as_str(),
+        None => path,
+    };
+
+    // asset paths are always relative to the site
+
+    let path = format!("/{}", path.trim_start_matches('/'));
+    Ok(full_url_from(&path, &context.output)?.path().into())
+}
+
+/// Get the Subresource Integrity value of a fingerprinted asset.
+pub(crate) fn asset_integrity(
+    context: &GeneratorContext,
+    path: &str,
+) -> Result<String, RenderError> {
+    context
+        .config
+        .manifest
+        .get(path)
+        .map(|entry| entry.integrity.clone())
+        .ok_or_else(|| {
+            RenderError::new(format!(
+                "No integrity value for '{}'. Only fingerprinted assets have one.",
+                path
+            ))
+        })
+}
+
+fn path_param(h: &Helper) -> Result<String, RenderError> {
+    Ok(h.param(0)
+        .ok_or_else(|| RenderError::new(format!("Missing path parameter for {}", h.name())))?
+        .value()
+        .as_str()
+        .ok_or_else(|| RenderError::new("Wrong value type of path. Must be string."))?
+        .into())
+}
+
+/// Resolve the URL of an asset through the asset manifest: `{{ asset_url "/assets/custom.css" }}`
+pub struct AssetUrlHelper {
+    pub context: GeneratorContextProvider,
+}
+
+impl HelperDef for AssetUrlHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars,
+        _: &'rc Context,
+        _: &mut RenderContext,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let path = path_param(h)?;
+        let url = self
+            .context
+            .with(|context| Ok(asset_url(context, &path)?))?;
+
+        out.write(&url)?;
+
+        Ok(())
+    }
+}
+
+/// Get the Subresource Integrity value of an asset: `{{ asset_integrity "/assets/custom.css" }}`
+pub struct AssetIntegrityHelper {
+    pub context: GeneratorContextProvider,
+}
+
+impl HelperDef for AssetIntegrityHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars,
+        _: &'rc Context,
+        _: &mut RenderContext,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let path = path_param(h)?;
+        let integrity = self
+            .context
+            .with(|context| Ok(asset_integrity(context, &path)?))?;
+
+        out.write(&integrity)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fingerprint::{Manifest, ManifestEntry};
+    use crate::generator::GeneratorConfig;
+
+    fn context() -> GeneratorContext {
+        let mut manifest = Manifest::default();
+        manifest.insert(
+            "/assets/custom.css",
+            ManifestEntry {
+                path: "/assets/custom.3f2a9c1e.css".into(),
+                integrity: "sha384-foo".into(),
+            },
+        );
+
+        let config = GeneratorConfig {
+            manifest: manifest.into(),
+            ..GeneratorConfig::for_test("http://localhost/base/")
+        };
+        let output = crate::generator::Output::new(
+            "http://localhost/base/",
+            "/foo/bar.html",
+            None::<String>,
+        )
+        .unwrap();
+
+        GeneratorContext::new(&config, &output)
+    }
+
+    #[test]
+    fn test_asset_url() -> Result<(), RenderError> {
+        let context = context();
+        assert_eq!(
+            asset_url(&context, "/assets/custom.css")?,
+            "/base/assets/custom.3f2a9c1e.css"
+        );
+        assert_eq!(
+            asset_url(&context, "assets/other.css")?,
+            "/base/assets/other.css"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_asset_integrity() {
+        let context = context();
+        assert_eq!(
+            asset_integrity(&context, "/assets/custom.css").ok(),
+            Some("sha384-foo".into())
+        );
+        assert!(asset_integrity(&context, "/assets/other.css").is_err());
+    }
+}
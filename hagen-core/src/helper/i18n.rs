@@ -0,0 +1,128 @@
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, RenderError,
+};
+use serde_json::{Map, Value};
+
+use crate::generator::{GeneratorContext, GeneratorContextProvider};
+
+/// Translate a message into the language of the current page.
+///
+/// Falls back to the default language of the site, if the message isn't translated. Pages of a
+/// single-language site use the catalog of `site.language`, or the only catalog present.
+pub(crate) fn translate(
+    context: &GeneratorContext,
+    key: &str,
+    args: &Map<String, Value>,
+) -> Result<String, RenderError> {
+    let mut languages: Vec<&str> = Vec::new();
+    if let Some(ref language) = context.output.language {
+        languages.push(language);
+    }
+    if let Some(language) = context.config.languages.first() {
+        if !languages.contains(&language.code.as_str()) {
+            languages.push(&language.code);
+        }
+    }
+
+    context
+        .config
+        .catalogs
+        .translate(&languages, key, args)
+        .ok_or_else(|| {
+            RenderError::new(format!(
+                "Missing translation for '{}' (languages: {})",
+                key,
+                languages.join(", ")
+            ))
+        })
+}
+
+/// Translate a message: `{{ t "comments" count=post.comments }}`
+///
+/// All hash parameters are available as placeholders in the message: `{count} comments`.
+pub struct TranslateHelper {
+    pub context: GeneratorContextProvider,
+}
+
+impl HelperDef for TranslateHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        r: &'reg Handlebars,
+        _: &'rc Context,
+        rc: &mut RenderContext,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let key = h
+            .param(0)
+            .ok_or_else(|| RenderError::new("Missing key parameter for t"))?
+            .value()
+            .as_str()
+            .ok_or_else(|| RenderError::new("Wrong value type of key. Must be string."))?;
+
+        let args: Map<String, Value> = h
+            .hash()
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.value().clone()))
+            .collect();
+
+        let message = self
+            .context
+            .with(|context| Ok(translate(context, key, &args)?))?;
+
+        // escaped like the output of a plain expression
+        if rc.is_disable_escape() {
+            out.write(&message)?;
+        } else {
+            out.write(&r.get_escape_fn()(&message))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::{GeneratorConfig, GeneratorContext, Output};
+    use crate::i18n::Catalogs;
+    use failure::Error;
+    use serde_json::json;
+    use std::sync::{Arc, RwLock};
+
+    #[test]
+    fn test_escape() -> Result<(), Error> {
+        let provider = Arc::new(RwLock::new(None));
+        let mut h = Handlebars::new();
+        h.register_helper(
+            "t",
+            Box::new(TranslateHelper {
+                context: GeneratorContextProvider::new(&provider),
+            }),
+        );
+
+        let mut catalogs = Catalogs::default();
+        catalogs.insert("en", json!({"greeting": "Hello <b>{name}</b>"}));
+        let mut config = GeneratorConfig::for_test("http://localhost/");
+        config.catalogs = Arc::new(catalogs);
+
+        let output = Output::new(config.basename.to_string(), "/", Option::None::<String>)?;
+        provider
+            .write()
+            .unwrap()
+            .replace(GeneratorContext::new(&config, &output));
+
+        let data = Map::new();
+        assert_eq!(
+            h.render_template(r#"{{ t "greeting" name="&" }}"#, &data)?,
+            "Hello &lt;b&gt;&amp;&lt;/b&gt;"
+        );
+
+        h.register_escape_fn(handlebars::no_escape);
+        assert_eq!(
+            h.render_template(r#"{{ t "greeting" name="&" }}"#, &data)?,
+            "Hello <b>&</b>"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+use handlebars::{
+    html_escape, Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext,
+    RenderError, Renderable,
+};
+
+use relative_path::RelativePath;
+
+use crate::generator::{GeneratorContext, GeneratorContextProvider};
+use crate::helper::url::full_url_from;
+use crate::images::{ImagePipeline, ProcessedImage};
+
+/// Process an image, creating all variants.
+///
+/// Paths starting with a `/` are relative to the site, others relative to the current page.
+pub(crate) fn process_image(
+    context: &GeneratorContext,
+    path: &str,
+) -> Result<ProcessedImage, RenderError> {
+    let base_path = full_url_from("/", &context.output)?.path().to_string();
+    let path = image_path(path, &context.output.path);
+    let path = path.as_str();
+
+    let options = match context.config.images {
+        Some(ref options) => options,
+        None => {
+            return Ok(ProcessedImage {
+                src: format!("{}{}", base_path, path),
+                width: 0,
+                height: 0,
+                srcset: String::new(),
+                sources: vec![],
+                files: vec![],
+            })
+        }
+    };
+
+    let pipeline = ImagePipeline {
+        root: &context.config.root,
+        output: &context.config.output,
+        sources: &context.config.sources,
+        options,
+    };
+
+    let image = pipeline
+        .process(path, &base_path)
+        .map_err(|err| RenderError::new(err.to_string()))?;
+
+    if let Ok(mut produced) = context.config.produced.lock() {
+        produced.extend(image.files.iter().cloned());
+    }
+
+    Ok(image)
+}
+
+/// Resolve the path of an image, relative to the site.
+fn image_path(path: &str, page: &str) -> String {
+    if let Some(path) = path.strip_prefix('/') {
+        return path.into();
+    }
+
+    RelativePath::new(page)
+        .parent()
+        .unwrap_or_else(|| RelativePath::new(""))
+        .join_normalized(path)
+        .into_string()
+}
+
+/// Create resized and converted variants of an image, relative to the current page or,
+/// starting with a `/`, to the site: `{{image "photo.jpg" alt="A photo"}}`
+///
+/// Used as a block, the image data (`src`, `width`, `height`, `srcset`, `sources`) is the context
+/// of the block. Otherwise an `<img>` tag is rendered, using an optional `alt` hash value.
+pub struct ImageHelper {
+    pub context: GeneratorContextProvider,
+}
+
+impl HelperDef for ImageHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        r: &'reg Handlebars,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let path = h
+            .param(0)
+            .ok_or_else(|| RenderError::new("Missing path parameter for 'image'"))?
+            .value()
+            .as_str()
+            .ok_or_else(|| RenderError::new("Wrong value type of path. Must be string."))?
+            .to_string();
+
+        let image = self
+            .context
+            .with(|context| Ok(process_image(context, &path)?))?;
+
+        match h.template() {
+            Some(t) => {
+                let mut rc = rc.clone();
+                rc.set_context(Context::wraps(&image)?);
+                t.render(r, ctx, &mut rc, out)?;
+            }
+            None => {
+                let alt = h.hash_get("alt").and_then(|v| v.value().as_str());
+                out.write(&format!(
+                    r#"<img src="{}" srcset="{}" width="{}" height="{}" alt="{}">"#,
+                    html_escape(&image.src),
+                    html_escape(&image.srcset),
+                    image.width,
+                    image.height,
+                    html_escape(alt.unwrap_or_default())
+                ))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_path() {
+        assert_eq!(
+            image_path("image.png", "blog/post/index.html"),
+            "blog/post/image.png"
+        );
+        assert_eq!(
+            image_path("../shared/image.png", "blog/post/index.html"),
+            "blog/shared/image.png"
+        );
+        assert_eq!(
+            image_path("/images/logo.png", "blog/post/index.html"),
+            "images/logo.png"
+        );
+        assert_eq!(image_path("logo.png", "index.html"), "logo.png");
+    }
+}
@@ -30,7 +30,7 @@ impl HelperDef for MarkdownifyHelper {
     }
 }
 
-fn render<S: AsRef<str>>(markdown_input: S) -> Result<String, RenderError> {
+pub(crate) fn render<S: AsRef<str>>(markdown_input: S) -> Result<String, RenderError> {
     let options = Options::all();
 
     let parser = Parser::new_ext(markdown_input.as_ref(), options);
@@ -1,4 +1,7 @@
+pub mod asset;
 pub mod basic;
+pub mod i18n;
+pub mod images;
 pub mod markdown;
 pub mod sort;
 pub mod time;
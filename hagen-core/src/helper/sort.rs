@@ -190,7 +190,7 @@ impl HelperDef for SortedHelper {
     }
 }
 
-fn sorted_array(list: &Vec<Value>, sort_path: &str, invert: bool) -> Vec<usize> {
+pub(crate) fn sorted_array(list: &Vec<Value>, sort_path: &str, invert: bool) -> Vec<usize> {
     struct Entry<'a> {
         i: usize,
         value: &'a Value,
@@ -212,7 +212,11 @@ fn sorted_array(list: &Vec<Value>, sort_path: &str, invert: bool) -> Vec<usize>
     e.iter().map(|i| i.i).collect()
 }
 
-fn sorted_map<'a>(map: &'a Map<String, Value>, sort_path: &str, invert: bool) -> Vec<&'a String> {
+pub(crate) fn sorted_map<'a>(
+    map: &'a Map<String, Value>,
+    sort_path: &str,
+    invert: bool,
+) -> Vec<&'a String> {
     struct Entry<'a> {
         key: &'a String,
         value: &'a Value,
@@ -2,10 +2,74 @@ use handlebars::{
     Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, RenderError,
 };
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Locale, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde_json::Value;
+use std::convert::TryFrom;
 
-#[derive(Clone, Copy)]
-pub struct TimeHelper;
+use crate::generator::{GeneratorContext, GeneratorContextProvider};
+
+/// Formats of timestamps without a time zone, which are accepted in addition to RFC 3339 and
+/// RFC 2822.
+const NAIVE_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+];
+
+/// Formats of plain dates.
+const DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%Y/%m/%d", "%d.%m.%Y"];
+
+/// Default regions of languages, for which the locale isn't simply `xx_XX`.
+const DEFAULT_REGIONS: &[(&str, &str)] = &[
+    ("en", "en_US"),
+    ("ja", "ja_JP"),
+    ("zh", "zh_CN"),
+    ("ko", "ko_KR"),
+    ("cs", "cs_CZ"),
+    ("da", "da_DK"),
+    ("el", "el_GR"),
+    ("sv", "sv_SE"),
+    ("uk", "uk_UA"),
+    ("pt", "pt_PT"),
+];
+
+/// Options of formatting a timestamp.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TimestampOptions {
+    /// The locale, like `de` or `de-CH`.
+    pub locale: Option<String>,
+    /// The time zone, like `Europe/Berlin`. Defaults to UTC.
+    pub tz: Option<String>,
+    /// Format the distance to `now`, like "3 days ago". Only available in English.
+    pub relative: bool,
+    /// The current time, defaults to the build timestamp.
+    pub now: Option<DateTime<Utc>>,
+    /// The language of the current page, used as locale if none is provided.
+    pub language: Option<String>,
+}
+
+impl TimestampOptions {
+    /// Fill in the build timestamp and page language, when rendering a page.
+    pub fn with_context(mut self, context: Option<&GeneratorContext>) -> Self {
+        if let Some(context) = context {
+            self.now = self.now.or(Some(context.config.build_time));
+            self.language = context.output.language.clone();
+        }
+        self
+    }
+}
+
+/// Format a timestamp: `{{timestamp "%e. %B %Y" date locale="de" tz="Europe/Berlin"}}`
+///
+/// Use `relative=true` for the distance to the build timestamp, like "3 days ago". Relative
+/// timestamps are always English, other locales and non-English pages are rejected, unless
+/// `locale="en"` is set.
+#[derive(Clone)]
+pub struct TimeHelper {
+    pub context: GeneratorContextProvider,
+}
 
 impl HelperDef for TimeHelper {
     fn call<'reg: 'rc, 'rc>(
@@ -25,27 +89,298 @@ impl HelperDef for TimeHelper {
 
         let value = h.param(1).map(|p| p.value());
 
-        let value = if let Some(timestamp) = value {
-            match timestamp.as_str() {
-                Some(s) => DateTime::parse_from_rfc3339(s)
-                    .map_err(|err| RenderError::with(err))?
-                    .with_timezone(&Utc),
-                _ => {
-                    return Err(RenderError::new(format!(
-                        "Timestamp is not a string: {:?}",
-                        timestamp
-                    )))
-                }
+        let string_hash = |name: &str| -> Result<Option<String>, RenderError> {
+            match h.hash_get(name).map(|v| v.value()) {
+                Some(Value::String(s)) => Ok(Some(s.clone())),
+                Some(Value::Null) | None => Ok(None),
+                Some(_) => Err(RenderError::new(format!("'{}' must be a string", name))),
             }
-        } else {
-            Utc::now()
         };
 
-        let result = value.format(format);
+        let options = TimestampOptions {
+            locale: string_hash("locale")?,
+            tz: string_hash("tz")?,
+            relative: h
+                .hash_get("relative")
+                .and_then(|v| v.value().as_bool())
+                .unwrap_or(false),
+            ..Default::default()
+        };
+        let options = self
+            .context
+            .with_optional(|context| Ok(options.with_context(context)))?;
 
-        let result = result.to_string();
+        let result = format_timestamp(format, value, &options)?;
         out.write(&result)?;
 
         Ok(())
     }
 }
+
+/// Format a timestamp value, or the current time if no value is provided.
+pub(crate) fn format_timestamp(
+    format: &str,
+    value: Option<&Value>,
+    options: &TimestampOptions,
+) -> Result<String, RenderError> {
+    let tz: Tz = match options.tz {
+        Some(ref tz) => tz
+            .parse()
+            .map_err(|err| RenderError::new(format!("Invalid time zone '{}': {}", tz, err)))?,
+        None => Tz::UTC,
+    };
+
+    let now = options.now.unwrap_or_else(Utc::now);
+
+    let value = match value {
+        Some(timestamp) => parse_timestamp(timestamp, &tz)?,
+        None => now,
+    };
+
+    if options.relative {
+        // an explicit locale overrides the language of the page
+        if let Some(locale) = options.locale.as_ref().or(options.language.as_ref()) {
+            if locale.split(['-', '_']).next() != Some("en") {
+                return Err(RenderError::new(format!(
+                    "Relative timestamps are only available in English, not in '{}' \
+                     (use locale=\"en\" to render them in English anyway)",
+                    locale
+                )));
+            }
+        }
+        return Ok(relative(&value, &now));
+    }
+
+    let value = value.with_timezone(&tz);
+
+    let locale = match (&options.locale, &options.language) {
+        (Some(locale), _) => Some(
+            parse_locale(locale)
+                .ok_or_else(|| RenderError::new(format!("Unknown locale: {}", locale)))?,
+        ),
+        // the page language is only a hint
+        (None, Some(language)) => parse_locale(language),
+        (None, None) => None,
+    };
+
+    Ok(match locale {
+        Some(locale) => value.format_localized(format, locale).to_string(),
+        None => value.format(format).to_string(),
+    })
+}
+
+/// Parse a timestamp. Values without a time zone are in the provided time zone.
+fn parse_timestamp(value: &Value, tz: &Tz) -> Result<DateTime<Utc>, RenderError> {
+    let s = match value {
+        Value::String(s) => s.trim(),
+        Value::Number(n) => {
+            // seconds since the epoch
+            return n
+                .as_i64()
+                .and_then(|secs| Utc.timestamp_opt(secs, 0).single())
+                .ok_or_else(|| RenderError::new(format!("Invalid timestamp: {}", n)));
+        }
+        _ => {
+            return Err(RenderError::new(format!(
+                "Timestamp is not a string: {:?}",
+                value
+            )))
+        }
+    };
+
+    if let Ok(result) = DateTime::parse_from_rfc3339(s) {
+        return Ok(result.with_timezone(&Utc));
+    }
+    if let Ok(result) = DateTime::parse_from_rfc2822(s) {
+        return Ok(result.with_timezone(&Utc));
+    }
+
+    let naive = NAIVE_FORMATS
+        .iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(s, f).ok())
+        .or_else(|| {
+            DATE_FORMATS
+                .iter()
+                .find_map(|f| NaiveDate::parse_from_str(s, f).ok())
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        })
+        .ok_or_else(|| RenderError::new(format!("Unable to parse timestamp: '{}'", s)))?;
+
+    tz.from_local_datetime(&naive)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+        .ok_or_else(|| RenderError::new(format!("Invalid local time: '{}' ({})", s, tz)))
+}
+
+/// Find the locale for a locale name or language code, like `de-CH` or `de`.
+fn parse_locale(name: &str) -> Option<Locale> {
+    let name = name.replace('-', "_");
+
+    if let Ok(locale) = Locale::try_from(name.as_str()) {
+        return Some(locale);
+    }
+
+    let name = DEFAULT_REGIONS
+        .iter()
+        .find(|(language, _)| *language == name)
+        .map(|(_, locale)| locale.to_string())
+        .unwrap_or_else(|| format!("{}_{}", name, name.to_uppercase()));
+
+    Locale::try_from(name.as_str()).ok()
+}
+
+/// Describe the distance between a timestamp and now, like "3 days ago" or "in 2 hours".
+fn relative(value: &DateTime<Utc>, now: &DateTime<Utc>) -> String {
+    let seconds = (*now - *value).num_seconds();
+    let distance = seconds.abs();
+
+    const MINUTE: i64 = 60;
+    const HOUR: i64 = 60 * MINUTE;
+    const DAY: i64 = 24 * HOUR;
+
+    let (n, unit) = match distance {
+        d if d < MINUTE => return "just now".into(),
+        d if d < HOUR => (d / MINUTE, "minute"),
+        d if d < DAY => (d / HOUR, "hour"),
+        d if d < 30 * DAY => (d / DAY, "day"),
+        d if d < 365 * DAY => (d / (30 * DAY), "month"),
+        d => (d / (365 * DAY), "year"),
+    };
+
+    let unit = if n == 1 {
+        unit.to_string()
+    } else {
+        format!("{}s", unit)
+    };
+
+    if seconds < 0 {
+        format!("in {} {}", n, unit)
+    } else {
+        format!("{} {} ago", n, unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2020, 4, 24, 12, 0, 0).unwrap()
+    }
+
+    fn format(format: &str, value: Value, options: TimestampOptions) -> String {
+        format_timestamp(format, Some(&value), &options).expect("Must format")
+    }
+
+    #[test]
+    fn test_formats() {
+        let options = TimestampOptions::default();
+        for value in &[
+            "2020-04-21T10:30:00Z",
+            "2020-04-21T12:30:00+02:00",
+            "Tue, 21 Apr 2020 10:30:00 +0000",
+            "2020-04-21 10:30:00",
+            "2020-04-21T10:30",
+        ] {
+            assert_eq!(
+                format("%Y-%m-%d %H:%M", json!(value), options.clone()),
+                "2020-04-21 10:30",
+                "{}",
+                value
+            );
+        }
+        assert_eq!(
+            format("%Y-%m-%d %H:%M", json!("2020-04-21"), options.clone()),
+            "2020-04-21 00:00"
+        );
+        assert_eq!(format("%Y", json!(1587465000), options), "2020");
+    }
+
+    #[test]
+    fn test_tz() {
+        let options = TimestampOptions {
+            tz: Some("Europe/Berlin".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            format("%H:%M", json!("2020-04-21T10:30:00Z"), options.clone()),
+            "12:30"
+        );
+        // naive values are in the time zone
+        assert_eq!(format("%H:%M", json!("2020-04-21 10:30"), options), "10:30");
+    }
+
+    #[test]
+    fn test_locale() {
+        let options = TimestampOptions {
+            locale: Some("de".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            format("%e. %B %Y", json!("2020-03-21"), options),
+            "21. März 2020"
+        );
+
+        let options = TimestampOptions {
+            language: Some("fr".into()),
+            ..Default::default()
+        };
+        assert_eq!(format("%B", json!("2020-04-21"), options), "avril");
+
+        let options = TimestampOptions {
+            locale: Some("xx".into()),
+            ..Default::default()
+        };
+        assert!(format_timestamp("%B", Some(&json!("2020-04-21")), &options).is_err());
+    }
+
+    #[test]
+    fn test_relative() {
+        let options = TimestampOptions {
+            relative: true,
+            now: Some(now()),
+            ..Default::default()
+        };
+        assert_eq!(
+            format("", json!("2020-04-21T12:00:00Z"), options.clone()),
+            "3 days ago"
+        );
+        assert_eq!(
+            format("", json!("2020-04-24T11:59:30Z"), options.clone()),
+            "just now"
+        );
+        assert_eq!(
+            format("", json!("2020-04-24T13:00:00Z"), options.clone()),
+            "in 1 hour"
+        );
+        assert_eq!(
+            format("", json!("2018-01-01"), options.clone()),
+            "2 years ago"
+        );
+
+        let english = TimestampOptions {
+            locale: Some("en-GB".into()),
+            ..options.clone()
+        };
+        assert_eq!(format("", json!("2018-01-01"), english), "2 years ago");
+
+        let german = TimestampOptions {
+            locale: Some("de".into()),
+            ..options.clone()
+        };
+        assert!(format_timestamp("", Some(&json!("2018-01-01")), &german).is_err());
+
+        // the language of a page applies as well, unless overridden
+        let german_page = TimestampOptions {
+            language: Some("de".into()),
+            ..options.clone()
+        };
+        assert!(format_timestamp("", Some(&json!("2018-01-01")), &german_page).is_err());
+        let english = TimestampOptions {
+            locale: Some("en".into()),
+            ..german_page
+        };
+        assert_eq!(format("", json!("2018-01-01"), english), "2 years ago");
+    }
+}
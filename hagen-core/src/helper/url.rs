@@ -191,6 +191,8 @@ mod tests {
             url: url.to_string(),
             path: path.into(),
             template: None,
+            language: None,
+            translations: Vec::new(),
         };
         assert_eq!(
             full_url_from(url, &o).expect(""),
@@ -265,11 +267,7 @@ mod tests {
             }),
         );
 
-        let config = GeneratorConfig {
-            basename: Url::parse(base)?,
-            root: "/tmp".into(),
-            output: "/tmp/output".into(),
-        };
+        let config = GeneratorConfig::for_test(base);
 
         let output = Output::new(config.basename.to_string(), path, Option::None::<String>)?;
         let ctx = GeneratorContext::new(&config, &output);
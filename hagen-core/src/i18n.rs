@@ -0,0 +1,262 @@
+use failure::Error;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::Path;
+
+use log::info;
+use serde_json::{Map, Value};
+
+use crate::loader::content_error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// String catalogs, one per language, loaded from `<language>.yaml` files.
+///
+/// Messages are looked up by key, nested keys use a dot: `post.published`. A message is either
+/// a string, or a map of plural forms (`zero`, `one`, `two`, `few`, `many`, `other`).
+#[derive(Debug, Clone, Default)]
+pub struct Catalogs {
+    catalogs: HashMap<String, Value>,
+    /// The language to fall back to, if no language of the page has a message.
+    default: Option<String>,
+}
+
+impl Catalogs {
+    /// Load all catalogs of a directory.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Catalogs> {
+        let mut result = Catalogs::default();
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+
+            let language = match (path.file_stem(), path.extension()) {
+                (Some(stem), Some(ext)) if ext == "yaml" || ext == "yml" => {
+                    stem.to_string_lossy().into_owned()
+                }
+                _ => continue,
+            };
+
+            info!("Loading translations ({}): {:?}", language, path);
+
+            let catalog: Value = serde_yaml::from_reader(File::open(&path)?)
+                .map_err(|err| content_error(&path, 0, err.into()))?;
+            result.insert(language, catalog);
+        }
+
+        Ok(result)
+    }
+
+    pub fn insert<S: Into<String>>(&mut self, language: S, catalog: Value) {
+        self.catalogs.insert(language.into(), catalog);
+    }
+
+    /// Set the language to fall back to. Without it, the only catalog present is used.
+    pub fn set_default(&mut self, language: Option<String>) {
+        self.default = language;
+    }
+
+    fn default_language(&self) -> Option<&str> {
+        match self.default {
+            Some(ref language) => Some(language),
+            None if self.catalogs.len() == 1 => self.catalogs.keys().next().map(|k| k.as_str()),
+            None => None,
+        }
+    }
+
+    /// Translate a message, trying the languages in order.
+    ///
+    /// Placeholders like `{count}` are replaced with the values of the arguments.
+    pub fn translate(
+        &self,
+        languages: &[&str],
+        key: &str,
+        args: &Map<String, Value>,
+    ) -> Option<String> {
+        let count = args.get("count").and_then(|c| c.as_f64());
+
+        languages
+            .iter()
+            .cloned()
+            .chain(self.default_language())
+            .filter_map(|language| {
+                let message = lookup(self.catalogs.get(language)?, key)?;
+                select(message, language, count)
+            })
+            .next()
+            .map(|message| interpolate(message, args))
+    }
+}
+
+/// Find a message by its key, either a plain key or a path of nested keys.
+fn lookup<'a>(catalog: &'a Value, key: &str) -> Option<&'a Value> {
+    if let Some(message) = catalog.get(key) {
+        return Some(message);
+    }
+
+    key.split('.')
+        .try_fold(catalog, |value, segment| value.get(segment))
+}
+
+/// Select the plural form of a message.
+fn select<'a>(message: &'a Value, language: &str, count: Option<f64>) -> Option<&'a str> {
+    let forms = match message {
+        Value::String(s) => return Some(s),
+        Value::Object(forms) => forms,
+        _ => return None,
+    };
+
+    let category = count.map(|n| plural_category(language, n));
+
+    let explicit_zero = match count {
+        Some(0.0) => forms.get("zero"),
+        _ => None,
+    };
+
+    explicit_zero
+        .or_else(|| category.and_then(|c| forms.get(c)))
+        .or_else(|| forms.get("other"))
+        .and_then(|m| m.as_str())
+}
+
+/// Get the CLDR plural category of a (cardinal) number, for the most common languages.
+pub fn plural_category(language: &str, n: f64) -> &'static str {
+    let language = language.split(['-', '_']).next().unwrap_or("");
+    let integer = n.fract() == 0.0 && n >= 0.0;
+    let i = n.abs() as u64;
+
+    match language {
+        "ja" | "zh" | "ko" | "vi" | "th" | "id" | "ms" => "other",
+        // the integer part is 0 or 1, including e.g. 1.5
+        "fr" | "pt" if i < 2 => "one",
+        "ru" | "uk" | "be" if integer => match (i % 10, i % 100) {
+            (1, r) if r != 11 => "one",
+            (2..=4, r) if !(12..=14).contains(&r) => "few",
+            _ => "many",
+        },
+        "pl" if integer => match (i, i % 10, i % 100) {
+            (1, _, _) => "one",
+            (_, 2..=4, r) if !(12..=14).contains(&r) => "few",
+            _ => "many",
+        },
+        "cs" | "sk" if integer => match i {
+            1 => "one",
+            2..=4 => "few",
+            _ => "other",
+        },
+        "fr" | "pt" | "ru" | "uk" | "be" | "pl" | "cs" | "sk" => "other",
+        _ if integer && i == 1 => "one",
+        _ => "other",
+    }
+}
+
+/// Replace `{name}` placeholders with the values of the arguments.
+fn interpolate(message: &str, args: &Map<String, Value>) -> String {
+    let mut result = message.to_string();
+
+    for (name, value) in args {
+        let value = match value {
+            Value::String(s) => s.clone(),
+            Value::Null => String::new(),
+            other => other.to_string(),
+        };
+        result = result.replace(&format!("{{{}}}", name), &value);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn catalogs() -> Catalogs {
+        let mut catalogs = Catalogs::default();
+        catalogs.insert(
+            "en",
+            json!({
+                "published": "Published on {date}",
+                "comments": {"zero": "No comments", "one": "{count} comment", "other": "{count} comments"},
+                "nav": {"home": "Home"},
+            }),
+        );
+        catalogs.insert(
+            "de",
+            json!({
+                "published": "Veröffentlicht am {date}",
+                "comments": {"one": "{count} Kommentar", "other": "{count} Kommentare"},
+            }),
+        );
+        catalogs
+    }
+
+    fn args(value: Value) -> Map<String, Value> {
+        value.as_object().cloned().unwrap_or_default()
+    }
+
+    #[test]
+    fn test_translate() {
+        let c = catalogs();
+        assert_eq!(
+            c.translate(&["de", "en"], "published", &args(json!({"date": "1.1."}))),
+            Some("Veröffentlicht am 1.1.".into())
+        );
+        assert_eq!(
+            c.translate(&["en"], "comments", &args(json!({"count": 1}))),
+            Some("1 comment".into())
+        );
+        assert_eq!(
+            c.translate(&["de"], "comments", &args(json!({"count": 0}))),
+            Some("0 Kommentare".into())
+        );
+        assert_eq!(
+            c.translate(&["en"], "comments", &args(json!({"count": 0}))),
+            Some("No comments".into())
+        );
+    }
+
+    #[test]
+    fn test_fallback() {
+        let c = catalogs();
+        assert_eq!(
+            c.translate(&["de", "en"], "nav.home", &Map::new()),
+            Some("Home".into())
+        );
+        assert_eq!(c.translate(&["de"], "nav.home", &Map::new()), None);
+        assert_eq!(c.translate(&["ja", "en"], "missing", &Map::new()), None);
+    }
+
+    #[test]
+    fn test_default_language() {
+        let mut c = catalogs();
+        assert_eq!(c.translate(&[], "nav.home", &Map::new()), None);
+        c.set_default(Some("en".into()));
+        assert_eq!(
+            c.translate(&[], "nav.home", &Map::new()),
+            Some("Home".into())
+        );
+
+        let mut single = Catalogs::default();
+        single.insert("de", json!({"home": "Startseite"}));
+        assert_eq!(
+            single.translate(&[], "home", &Map::new()),
+            Some("Startseite".into())
+        );
+    }
+
+    #[test]
+    fn test_plural_category() {
+        assert_eq!(plural_category("en", 1.0), "one");
+        assert_eq!(plural_category("en", 2.0), "other");
+        assert_eq!(plural_category("en", 1.5), "other");
+        assert_eq!(plural_category("fr", 0.0), "one");
+        assert_eq!(plural_category("fr", 1.5), "one");
+        assert_eq!(plural_category("fr", 2.0), "other");
+        assert_eq!(plural_category("ja", 1.0), "other");
+        assert_eq!(plural_category("ru", 21.0), "one");
+        assert_eq!(plural_category("ru", 22.0), "few");
+        assert_eq!(plural_category("ru", 12.0), "many");
+        assert_eq!(plural_category("pl", 25.0), "many");
+        assert_eq!(plural_category("de-CH", 1.0), "one");
+    }
+}
@@ -0,0 +1,160 @@
+use failure::Error;
+use std::path::Path;
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use log::debug;
+
+use crate::rules::IgnoreConfig;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// The name of the per-directory ignore file, using the gitignore syntax.
+pub const IGNORE_FILE: &str = ".hagenignore";
+
+/// Gitignore style ignore rules, from the configuration and `.hagenignore` files.
+#[derive(Clone, Default)]
+pub struct Ignores {
+    /// The matchers, from the outermost to the innermost directory.
+    matchers: Vec<Gitignore>,
+    include_hidden: bool,
+    /// Names of hidden files and directories, which are processed anyway.
+    hidden: Vec<String>,
+}
+
+impl Ignores {
+    /// Create the ignore rules for a directory tree.
+    ///
+    /// Patterns of the configuration are relative to the root of the tree.
+    pub fn new<P: AsRef<Path>>(root: P, config: &IgnoreConfig) -> Result<Self> {
+        let mut builder = GitignoreBuilder::new(root);
+        for p in &config.patterns {
+            builder.add_line(None, p)?;
+        }
+
+        Ok(Ignores {
+            matchers: vec![builder.build()?],
+            include_hidden: config.include_hidden,
+            hidden: Vec::new(),
+        })
+    }
+
+    /// Process the hidden files and directories with these names, like `.well-known`.
+    pub fn with_hidden(mut self, names: &[String]) -> Self {
+        self.hidden.extend(names.iter().cloned());
+        self
+    }
+
+    /// Get the rules for a directory, adding the rules of its `.hagenignore` file.
+    pub fn for_dir<P: AsRef<Path>>(&self, dir: P) -> Result<Self> {
+        let dir = dir.as_ref();
+        let file = dir.join(IGNORE_FILE);
+
+        if !file.is_file() {
+            return Ok(self.clone());
+        }
+
+        debug!("Loading ignore file: {:?}", file);
+
+        let mut builder = GitignoreBuilder::new(dir);
+        if let Some(err) = builder.add(&file) {
+            return Err(err.into());
+        }
+
+        let mut result = self.clone();
+        result.matchers.push(builder.build()?);
+        Ok(result)
+    }
+
+    /// Check if a path should be ignored.
+    ///
+    /// The innermost matching pattern wins, so that a `.hagenignore` file can whitelist
+    /// (`!pattern`) entries ignored by an outer directory.
+    pub fn is_ignored<P: AsRef<Path>>(&self, path: P, is_dir: bool) -> bool {
+        let path = path.as_ref();
+
+        if path.file_name() == Some(IGNORE_FILE.as_ref()) {
+            return true;
+        }
+
+        if !self.include_hidden && is_hidden(path) && !self.is_allowed(path) {
+            return true;
+        }
+
+        for m in self.matchers.iter().rev() {
+            match m.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+
+        false
+    }
+
+    fn is_allowed(&self, path: &Path) -> bool {
+        path.file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| self.hidden.iter().any(|h| h == n))
+    }
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .map(|n| n.starts_with('.') && n != "." && n != "..")
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ignores(patterns: &[&str]) -> Ignores {
+        ignores_with(patterns, false)
+    }
+
+    fn ignores_with(patterns: &[&str], include_hidden: bool) -> Ignores {
+        let config = IgnoreConfig {
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+            include_hidden,
+        };
+        Ignores::new("/site/content", &config).unwrap()
+    }
+
+    #[test]
+    fn test_hidden() {
+        let i = ignores(&[]);
+        assert!(i.is_ignored("/site/content/.git", true));
+        assert!(i.is_ignored("/site/content/blog/.post.md.swp", false));
+        assert!(!i.is_ignored("/site/content/blog/post.md", false));
+    }
+
+    #[test]
+    fn test_include_hidden() {
+        let i = ignores_with(&[".git/"], true);
+        assert!(!i.is_ignored("/site/content/.nojekyll", false));
+        assert!(!i.is_ignored("/site/content/.well-known", true));
+        assert!(i.is_ignored("/site/content/.git", true));
+        assert!(i.is_ignored("/site/content/.hagenignore", false));
+    }
+
+    #[test]
+    fn test_allowed_hidden() {
+        let i = ignores(&[]).with_hidden(&[".well-known".into(), ".htaccess".into()]);
+        assert!(!i.is_ignored("/site/content/.well-known", true));
+        assert!(!i.is_ignored("/site/content/blog/.htaccess", false));
+        assert!(i.is_ignored("/site/content/.DS_Store", false));
+        assert!(i.is_ignored("/site/content/.post.md.swp", false));
+    }
+
+    #[test]
+    fn test_patterns() {
+        let i = ignores(&["node_modules/", "*~", "/drafts"]);
+        assert!(i.is_ignored("/site/content/foo/node_modules", true));
+        assert!(!i.is_ignored("/site/content/foo/node_modules", false));
+        assert!(i.is_ignored("/site/content/foo/post.md~", false));
+        assert!(i.is_ignored("/site/content/drafts", true));
+        assert!(!i.is_ignored("/site/content/blog/drafts", true));
+    }
+}
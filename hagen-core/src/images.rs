@@ -0,0 +1,323 @@
+use failure::Error;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::{ColorType, DynamicImage};
+use log::{debug, info, warn};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::error::GeneratorError;
+use crate::rules::{ImageFormat, ImageOptions};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// A processed image, as exposed to templates.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessedImage {
+    /// The URL path of the original image.
+    pub src: String,
+    pub width: u32,
+    pub height: u32,
+    /// The `srcset` of the image, in its original format.
+    pub srcset: String,
+    /// Additional formats, for use with `<picture>` and `<source>`.
+    pub sources: Vec<ImageSource>,
+    /// The files of all variants, relative to the output directory.
+    #[serde(skip)]
+    pub files: Vec<String>,
+}
+
+/// The variants of an image in an additional format.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageSource {
+    #[serde(rename = "type")]
+    pub mime_type: String,
+    pub srcset: String,
+}
+
+/// Creates resized and converted variants of images in the output directory.
+///
+/// Encoded variants are cached in the root of the site, so that they can be re-used by
+/// later builds.
+pub struct ImagePipeline<'a> {
+    pub root: &'a Path,
+    pub output: &'a Path,
+    /// The source directories of output paths, see `GeneratorConfig::sources`.
+    pub sources: &'a [(String, PathBuf)],
+    pub options: &'a ImageOptions,
+}
+
+impl<'a> ImagePipeline<'a> {
+    /// Process an image, read from the asset or content directory it gets copied from.
+    ///
+    /// Images which don't have a source directory (e.g. produced by a build step) must
+    /// already be present in the output directory.
+    ///
+    /// The path is relative to the site, URLs are prefixed with the base path of the site.
+    pub fn process(&self, path: &str, base_path: &str) -> Result<ProcessedImage> {
+        let relative = path.trim_start_matches('/');
+        let source = self.source(relative);
+
+        let (width, height) = image::image_dimensions(&source).map_err(|err| {
+            GeneratorError::Error(format!("Failed to read image {:?}: {}", source, err))
+        })?;
+
+        // never scale up
+
+        let mut widths: Vec<u32> = self
+            .options
+            .widths
+            .iter()
+            .cloned()
+            .filter(|w| *w > 0 && *w < width)
+            .collect();
+        widths.push(width);
+        widths.sort();
+        widths.dedup();
+
+        let original = extension(relative);
+        let data = fs::read(&source)?;
+        let hash = format!("{:x}", Sha256::digest(&data));
+        let mut decoded: Option<DynamicImage> = None;
+        let mut files = Vec::new();
+
+        let mut variants = |ext: &str| -> Result<String> {
+            let mut srcset = Vec::with_capacity(widths.len());
+
+            for w in &widths {
+                let name = variant_name(relative, *w, width, ext);
+                let target = self.output.join(&name);
+
+                if name == relative && !target.exists() {
+                    // the original, which may not have been copied yet
+                    if let Some(parent) = target.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::copy(&source, &target)?;
+                } else if !target.exists() {
+                    let h = ((height as u64 * *w as u64) / width as u64).max(1) as u32;
+                    let cache = self.root.join(&self.options.cache).join(format!(
+                        "{}-{}-{}.{}",
+                        &hash[..16],
+                        w,
+                        self.options.quality,
+                        ext
+                    ));
+
+                    if !cache.exists() {
+                        if decoded.is_none() {
+                            debug!("Decoding image: {:?}", source);
+                            decoded = Some(image::load_from_memory(&data)?);
+                        }
+                        let image = decoded.as_ref().unwrap();
+
+                        info!("Creating image variant: {} ({}x{})", name, w, h);
+                        let resized = if *w == width {
+                            image.clone()
+                        } else {
+                            image.resize_exact(*w, h, FilterType::Lanczos3)
+                        };
+
+                        if let Some(parent) = cache.parent() {
+                            fs::create_dir_all(parent)?;
+                        }
+                        encode(&resized, ext, self.options.quality, &cache)?;
+                    }
+
+                    if let Some(parent) = target.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::copy(&cache, &target)?;
+                }
+
+                srcset.push(format!("{}{} {}w", base_path, name, w));
+                files.push(name);
+            }
+
+            Ok(srcset.join(", "))
+        };
+
+        let srcset = variants(&original)?;
+
+        let mut sources = Vec::new();
+        for format in &self.options.formats {
+            let ext = match format {
+                ImageFormat::Webp => "webp",
+                ImageFormat::Avif if cfg!(feature = "avif") => "avif",
+                ImageFormat::Avif => {
+                    warn!("AVIF output requires hagen to be built with the 'avif' feature");
+                    continue;
+                }
+            };
+            if ext == original {
+                continue;
+            }
+            sources.push(ImageSource {
+                mime_type: mime_type(ext).into(),
+                srcset: variants(ext)?,
+            });
+        }
+
+        Ok(ProcessedImage {
+            src: format!("{}{}", base_path, relative),
+            width,
+            height,
+            srcset,
+            sources,
+            files,
+        })
+    }
+
+    /// Find the source file of an output path, falling back to the output directory.
+    fn source(&self, relative: &str) -> PathBuf {
+        self.sources
+            .iter()
+            .filter_map(|(prefix, dir)| {
+                relative
+                    .strip_prefix(prefix.as_str())
+                    .map(|path| dir.join(path))
+            })
+            .find(|file| file.is_file())
+            .unwrap_or_else(|| self.output.join(relative))
+    }
+}
+
+/// The name of a variant: `photo.jpg` -> `photo-480w.webp`. The original keeps its name.
+fn variant_name(path: &str, width: u32, original_width: u32, ext: &str) -> String {
+    let (stem, original_ext) = match path.rfind('.') {
+        Some(idx) if !path[idx..].contains('/') => (&path[..idx], &path[idx + 1..]),
+        _ => (path, ""),
+    };
+
+    if width == original_width {
+        if original_ext.eq_ignore_ascii_case(ext) {
+            path.into()
+        } else {
+            format!("{}.{}", stem, ext)
+        }
+    } else {
+        format!("{}-{}w.{}", stem, width, ext)
+    }
+}
+
+fn extension(path: &str) -> String {
+    Path::new(path)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+fn mime_type(ext: &str) -> &'static str {
+    match ext {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        _ => "application/octet-stream",
+    }
+}
+
+fn encode(image: &DynamicImage, ext: &str, quality: u8, target: &Path) -> Result<()> {
+    match ext {
+        "jpg" | "jpeg" => {
+            let mut writer = BufWriter::new(File::create(target)?);
+            JpegEncoder::new_with_quality(&mut writer, quality).encode_image(image)?;
+        }
+        "webp" => {
+            // the pure Rust encoder only supports lossless encoding
+            let rgba = image.to_rgba8();
+            let writer = BufWriter::new(File::create(target)?);
+            WebPEncoder::new_lossless(writer).encode(
+                &rgba,
+                rgba.width(),
+                rgba.height(),
+                ColorType::Rgba8,
+            )?;
+        }
+        #[cfg(feature = "avif")]
+        "avif" => {
+            use image::codecs::avif::AvifEncoder;
+            use image::ImageEncoder;
+
+            let rgba = image.to_rgba8();
+            let writer = BufWriter::new(File::create(target)?);
+            AvifEncoder::new_with_speed_quality(writer, 8, quality).write_image(
+                &rgba,
+                rgba.width(),
+                rgba.height(),
+                ColorType::Rgba8,
+            )?;
+        }
+        _ => image.save(target)?,
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variant_name() {
+        assert_eq!(
+            variant_name("assets/photo.jpg", 480, 1920, "jpg"),
+            "assets/photo-480w.jpg"
+        );
+        assert_eq!(
+            variant_name("assets/photo.jpg", 480, 1920, "webp"),
+            "assets/photo-480w.webp"
+        );
+        assert_eq!(
+            variant_name("assets/photo.jpg", 1920, 1920, "jpg"),
+            "assets/photo.jpg"
+        );
+        assert_eq!(
+            variant_name("assets/photo.JPG", 1920, 1920, "webp"),
+            "assets/photo.webp"
+        );
+    }
+
+    #[test]
+    fn test_mime_type() {
+        assert_eq!(mime_type("jpg"), "image/jpeg");
+        assert_eq!(mime_type("webp"), "image/webp");
+    }
+
+    #[test]
+    fn test_source() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("hagen-images-{}", std::process::id()));
+        fs::create_dir_all(dir.join("content/post"))?;
+        fs::write(dir.join("content/post/photo.jpg"), "")?;
+
+        let sources = vec![
+            ("assets/".to_string(), dir.join("assets")),
+            ("".to_string(), dir.join("content")),
+        ];
+        let options = ImageOptions::default();
+        let pipeline = ImagePipeline {
+            root: &dir,
+            output: &dir.join("output"),
+            sources: &sources,
+            options: &options,
+        };
+
+        let content = pipeline.source("post/photo.jpg");
+        let output = pipeline.source("assets/logo.png");
+
+        fs::remove_dir_all(&dir)?;
+
+        assert_eq!(content, dir.join("content/post/photo.jpg"));
+        assert_eq!(output, dir.join("output/assets/logo.png"));
+
+        Ok(())
+    }
+}
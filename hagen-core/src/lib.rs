@@ -1,9 +1,21 @@
+mod clean;
+mod compress;
 mod copy;
+mod engine;
+mod fingerprint;
 mod helper;
+mod i18n;
+mod ignores;
+mod images;
 mod loader;
+mod minify;
 mod path;
 mod processor;
+mod redirect;
 mod rules;
+mod sass;
+mod theme;
 
+pub mod diagnostic;
 pub mod error;
 pub mod generator;
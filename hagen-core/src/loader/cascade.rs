@@ -0,0 +1,198 @@
+use failure::Error;
+use globset::{Glob, GlobMatcher};
+use serde_json::{Map, Value};
+
+use crate::rules::Cascade;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// The name of the file, providing default front matter values for all content of a directory.
+pub const DEFAULTS_FILE: &str = "_defaults.yaml";
+
+/// Check if content of this type carries front matter, and so receives defaults.
+///
+/// Only the loaders of Markdown and HTML files parse front matter. All other content, like YAML
+/// data or plain text, must not be turned into pages by e.g. a default `layout`.
+pub fn has_front_matter(type_name: &str) -> bool {
+    matches!(type_name, "md" | "html")
+}
+
+/// Deep merge default values into the front matter. Existing values take precedence.
+pub fn merge_defaults(defaults: &Map<String, Value>, values: &mut Map<String, Value>) {
+    for (k, default) in defaults {
+        match (values.get_mut(k), default) {
+            (None, _) => {
+                values.insert(k.clone(), default.clone());
+            }
+            (Some(Value::Object(value)), Value::Object(default)) => merge_defaults(default, value),
+            (Some(_), _) => {}
+        }
+    }
+}
+
+/// Cascade rules, applying default front matter values to content matching a path glob.
+pub struct CascadeRules {
+    rules: Vec<(GlobMatcher, Map<String, Value>)>,
+}
+
+impl CascadeRules {
+    pub fn new(cascade: &[Cascade]) -> Result<Self> {
+        let mut rules = Vec::with_capacity(cascade.len());
+
+        for c in cascade {
+            rules.push((Glob::new(&c.path)?.compile_matcher(), c.values.clone()));
+        }
+
+        Ok(CascadeRules { rules })
+    }
+
+    /// Apply the rules to all entries of a content tree.
+    ///
+    /// Later rules take precedence over earlier ones, values of the content itself (including
+    /// the values from the directory defaults) take precedence over all rules.
+    pub fn apply(&self, content: &mut Value) {
+        if self.rules.is_empty() {
+            return;
+        }
+
+        let type_name = content
+            .pointer("/metadata/type")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string();
+
+        if type_name == "directory" {
+            if let Some(Value::Object(children)) = content.get_mut("content") {
+                for child in children.values_mut() {
+                    self.apply(child);
+                }
+            }
+            return;
+        }
+
+        if !has_front_matter(&type_name) {
+            return;
+        }
+
+        let path = content_path(content);
+
+        if let Some(Value::Object(front_matter)) = content.get_mut("frontMatter") {
+            for (glob, values) in self.rules.iter().rev() {
+                if glob.is_match(&path) {
+                    merge_defaults(values, front_matter);
+                }
+            }
+        }
+    }
+}
+
+/// Get the path of a content entry, relative to the content directory.
+fn content_path(content: &Value) -> String {
+    let parent = content
+        .pointer("/metadata/parent")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .trim_matches('/');
+    let filename = content
+        .pointer("/metadata/filename")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+
+    if parent.is_empty() {
+        filename.into()
+    } else {
+        format!("{}/{}", parent, filename)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn map(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(m) => m,
+            _ => panic!("Not an object"),
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let defaults = map(json!({
+            "layout": "documentation",
+            "author": { "name": "Foo", "email": "foo@bar" },
+        }));
+        let mut values = map(json!({
+            "title": "Intro",
+            "author": { "name": "Bar" },
+        }));
+
+        merge_defaults(&defaults, &mut values);
+
+        assert_eq!(
+            Value::Object(values),
+            json!({
+                "title": "Intro",
+                "layout": "documentation",
+                "author": { "name": "Bar", "email": "foo@bar" },
+            })
+        );
+    }
+
+    #[test]
+    fn test_cascade() -> Result<()> {
+        let rules = CascadeRules::new(&[
+            Cascade {
+                path: "documentation/**".into(),
+                values: map(json!({"layout": "documentation", "toc": true})),
+            },
+            Cascade {
+                path: "documentation/api/*".into(),
+                values: map(json!({"layout": "api"})),
+            },
+        ])?;
+
+        let mut content = json!({
+            "metadata": { "parent": "/", "filename": "documentation", "type": "directory" },
+            "frontMatter": {},
+            "content": {
+                "intro": {
+                    "metadata": { "parent": "/documentation", "filename": "intro.md", "type": "md" },
+                    "frontMatter": { "toc": false },
+                },
+                "notes": {
+                    "metadata": { "parent": "/documentation", "filename": "notes.txt", "type": "txt" },
+                    "frontMatter": {},
+                },
+                "api": {
+                    "metadata": { "parent": "/documentation", "filename": "api", "type": "directory" },
+                    "frontMatter": {},
+                    "content": {
+                        "index": {
+                            "metadata": { "parent": "/documentation/api", "filename": "index.md", "type": "md" },
+                            "frontMatter": {},
+                        }
+                    }
+                }
+            }
+        });
+
+        rules.apply(&mut content);
+
+        assert_eq!(
+            content.pointer("/content/intro/frontMatter"),
+            Some(&json!({"toc": false, "layout": "documentation"}))
+        );
+        assert_eq!(
+            content.pointer("/content/notes/frontMatter"),
+            Some(&json!({}))
+        );
+        assert_eq!(
+            content.pointer("/content/api/content/index/frontMatter"),
+            Some(&json!({"toc": true, "layout": "api"}))
+        );
+
+        Ok(())
+    }
+}
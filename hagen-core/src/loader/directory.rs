@@ -1,4 +1,4 @@
-use log::{debug, info};
+use log::{debug, info, warn};
 use std::fs;
 
 use std::path::Path;
@@ -6,10 +6,16 @@ use std::path::Path;
 use failure::Error;
 type Result<T> = std::result::Result<T, Error>;
 
-use crate::loader::{detect, BodyProvider, Content, Loader, Metadata};
+use crate::error::GeneratorError;
+use crate::ignores::Ignores;
+use crate::loader::cascade::{has_front_matter, merge_defaults, DEFAULTS_FILE};
+use crate::loader::language::LanguageFilter;
+use crate::loader::{content_error, detect, BodyProvider, Content, Loader, Metadata, Resource};
+use crate::rules::Collisions;
 use serde_json::{Map, Value};
 use std::collections::BTreeMap;
 use std::fmt::Debug;
+use std::fs::File;
 
 pub struct DirectoryBodyProvider {
     body: BTreeMap<String, Content>,
@@ -30,11 +36,60 @@ impl BodyProvider for DirectoryBodyProvider {
 pub struct DirectoryLoader<P1: AsRef<Path>, P2: AsRef<Path>> {
     root: P1,
     path: P2,
+    /// Front matter defaults, inherited from the parent directories.
+    defaults: Map<String, Value>,
+    /// Ignore rules, inherited from the parent directories.
+    ignores: Ignores,
+    collisions: Collisions,
+    /// Only load the content files of a single language.
+    language: Option<LanguageFilter>,
 }
 
 impl<P1: AsRef<Path>, P2: AsRef<Path>> DirectoryLoader<P1, P2> {
     pub fn new(root: P1, path: P2) -> Self {
-        DirectoryLoader { root, path }
+        DirectoryLoader {
+            root,
+            path,
+            defaults: Map::new(),
+            ignores: Default::default(),
+            collisions: Default::default(),
+            language: None,
+        }
+    }
+
+    /// Only load the content files of a single language.
+    pub fn with_language(mut self, language: Option<LanguageFilter>) -> Self {
+        self.language = language;
+        self
+    }
+
+    /// Set how to handle children with the same name.
+    pub fn with_collisions(mut self, collisions: Collisions) -> Self {
+        self.collisions = collisions;
+        self
+    }
+
+    /// Set the rules for ignoring files.
+    pub fn with_ignores(mut self, ignores: Ignores) -> Self {
+        self.ignores = ignores;
+        self
+    }
+
+    /// Load the defaults of this directory, merged with the inherited defaults.
+    fn load_defaults(&self) -> Result<Map<String, Value>> {
+        let path = self.path.as_ref().join(DEFAULTS_FILE);
+
+        if !path.is_file() {
+            return Ok(self.defaults.clone());
+        }
+
+        info!("Loading - defaults: {:?}", path);
+
+        let mut defaults: Map<String, Value> = serde_yaml::from_reader(File::open(&path)?)
+            .map_err(|err| content_error(&path, 0, err.into()))?;
+        merge_defaults(&self.defaults, &mut defaults);
+
+        Ok(defaults)
     }
 }
 
@@ -43,25 +98,311 @@ impl<P1: AsRef<Path> + Debug, P2: AsRef<Path> + Debug> Loader for DirectoryLoade
         let path = self.path.as_ref();
         info!("Loading - directory: {:?}", path);
 
-        let mut content: BTreeMap<String, Content> = BTreeMap::new();
+        let defaults = self.load_defaults()?;
+        let ignores = self.ignores.for_dir(path)?;
+        let mut children: Vec<Content> = Vec::new();
+        let mut resources: Vec<Resource> = Vec::new();
+        // resources of subdirectories without pages
+        let mut nested: Vec<Resource> = Vec::new();
 
         for entry in fs::read_dir(path)? {
             debug!("  Child: {:?}", entry);
 
             let entry = entry?;
 
+            if entry.file_name() == DEFAULTS_FILE {
+                continue;
+            }
+
             let path = entry.path();
-            if let Some(loader) = detect(&self.root, &path) {
-                let child = loader.load_from()?;
-                let child_name = child.metadata.name.clone();
-                content.insert(child_name, child);
+
+            if ignores.is_ignored(&path, path.is_dir()) {
+                debug!("  Ignoring: {:?}", path);
+                continue;
+            }
+
+            let child = if path.is_dir() {
+                Some(
+                    DirectoryLoader {
+                        root: self.root.as_ref(),
+                        path: &path,
+                        defaults: defaults.clone(),
+                        ignores: ignores.clone(),
+                        collisions: self.collisions,
+                        language: self.language.clone(),
+                    }
+                    .load_from()?,
+                )
+            } else if let Some(loader) = detect(&self.root, &path) {
+                let stem = path.file_stem().and_then(|s| s.to_str());
+                let name = match (&self.language, stem) {
+                    (Some(language), Some(stem)) => match language.name_of(stem) {
+                        Some(name) => Some(name),
+                        None => {
+                            debug!("  Other language: {:?}", path);
+                            continue;
+                        }
+                    },
+                    _ => None,
+                };
+
+                let mut child = loader.load_from()?;
+                if let Some(name) = name {
+                    child.metadata.name = name;
+                }
+                if has_front_matter(&child.metadata.type_name) {
+                    merge_defaults(&defaults, &mut child.front_matter);
+                }
+                Some(child)
+            } else {
+                debug!("  Resource: {:?}", path);
+                resources.push(Resource::from_path(&self.root, &path));
+                None
+            };
+
+            if let Some(mut child) = child {
+                if let Some(ref language) = self.language {
+                    child.metadata.language = Some(language.language().into());
+                }
+                if path.is_dir() {
+                    for mut resource in child.resources.drain(..) {
+                        resource.name = format!("{}/{}", child.metadata.filename, resource.name);
+                        nested.push(resource);
+                    }
+                }
+                children.push(child);
             }
         }
 
+        let unclaimed = assign_resources(&mut children, resources, nested);
+
+        // pass on the resources of a directory without pages, to the bundle of the parent
+
+        let pages = children
+            .iter()
+            .any(|c| has_front_matter(&c.metadata.type_name));
+        let resources = if pages {
+            for r in &unclaimed {
+                debug!("  Resource without page: {:?}", r.path);
+            }
+            Vec::new()
+        } else {
+            unclaimed
+        };
+
+        let content = index(path, children, self.collisions)?;
+
         Ok(Content {
             metadata: Metadata::from_path(&self.root, path, path.file_name(), "directory"),
             front_matter: Map::new(),
             content: Box::new(DirectoryBodyProvider { body: content }),
+            resources,
         })
     }
 }
+
+/// Assign the non-content files of a directory to its pages, returning the unassigned ones.
+///
+/// A file belongs to the page with the same name (`post.md` and `post.png`), otherwise to
+/// the index page of the directory (a page bundle, like `post/index.md` and `post/image.png`).
+/// The index page also gets the files of subdirectories which contain no pages.
+fn assign_resources(
+    children: &mut [Content],
+    mut resources: Vec<Resource>,
+    nested: Vec<Resource>,
+) -> Vec<Resource> {
+    let page = |c: &Content, name: &str| {
+        has_front_matter(&c.metadata.type_name) && c.metadata.name == name
+    };
+    let index = children.iter().position(|c| page(c, "index"));
+
+    resources.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut owners = Vec::with_capacity(resources.len() + nested.len());
+    for resource in resources {
+        let stem = Path::new(&resource.name)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default()
+            .to_string();
+        let owner = children.iter().position(|c| page(c, &stem)).or(index);
+        owners.push((owner, resource));
+    }
+    owners.extend(nested.into_iter().map(|r| (index, r)));
+
+    let mut unclaimed = Vec::new();
+    for (owner, resource) in owners {
+        match owner {
+            Some(i) => children[i].resources.push(resource),
+            None => unclaimed.push(resource),
+        }
+    }
+
+    for child in children.iter_mut() {
+        child.resources.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    unclaimed
+}
+
+/// Key the children by name, handling name collisions (like `index.md` and `index.html`).
+fn index(
+    dir: &Path,
+    children: Vec<Content>,
+    collisions: Collisions,
+) -> Result<BTreeMap<String, Content>> {
+    let mut names: BTreeMap<String, Vec<Content>> = BTreeMap::new();
+    for child in children {
+        names
+            .entry(child.metadata.name.clone())
+            .or_default()
+            .push(child);
+    }
+
+    let mut result = BTreeMap::new();
+
+    for (name, mut entries) in names {
+        if entries.len() == 1 {
+            result.insert(name, entries.remove(0));
+            continue;
+        }
+
+        let mut files: Vec<String> = entries
+            .iter()
+            .map(|c| {
+                dir.join(&c.metadata.filename)
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        files.sort();
+
+        match collisions {
+            Collisions::Warn => {
+                entries.sort_by(|a, b| a.metadata.filename.cmp(&b.metadata.filename));
+                let last = entries.pop().expect("More than one entry");
+                warn!(
+                    "Content name collision: '{}' is used by: {} - using {}",
+                    name,
+                    files.join(", "),
+                    last.metadata.filename
+                );
+                result.insert(name, last);
+            }
+            Collisions::Fail => {
+                return Err(GeneratorError::Error(format!(
+                    "Content name collision: '{}' is used by: {}",
+                    name,
+                    files.join(", ")
+                ))
+                .into());
+            }
+            Collisions::Disambiguate => {
+                warn!(
+                    "Content name collision: '{}' is used by: {} - using file names instead",
+                    name,
+                    files.join(", ")
+                );
+                for c in entries {
+                    result.insert(c.metadata.filename.clone(), c);
+                }
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::JsonBodyProvider;
+
+    fn content(filename: &str) -> Content {
+        let path = Path::new("/content/blog").join(filename);
+        Content {
+            metadata: Metadata::from_path("/content", &path, path.file_stem(), "md"),
+            front_matter: Map::new(),
+            content: Box::new(JsonBodyProvider::new(Value::Null)),
+            resources: Vec::new(),
+        }
+    }
+
+    fn children() -> Vec<Content> {
+        vec![
+            content("index.md"),
+            content("index.html"),
+            content("post.md"),
+        ]
+    }
+
+    #[test]
+    fn test_collision_fail() {
+        let result = index(Path::new("/content/blog"), children(), Collisions::Fail);
+        let err = result.err().expect("Must fail").to_string();
+        assert!(err.contains("/content/blog/index.html"), "{}", err);
+        assert!(err.contains("/content/blog/index.md"), "{}", err);
+    }
+
+    #[test]
+    fn test_collision_warn() -> Result<()> {
+        let result = index(Path::new("/content/blog"), children(), Collisions::Warn)?;
+        let keys: Vec<&str> = result.keys().map(|k| k.as_str()).collect();
+        assert_eq!(keys, vec!["index", "post"]);
+        assert_eq!(result["index"].metadata.filename, "index.md");
+        Ok(())
+    }
+
+    #[test]
+    fn test_collision_disambiguate() -> Result<()> {
+        let result = index(
+            Path::new("/content/blog"),
+            children(),
+            Collisions::Disambiguate,
+        )?;
+        let keys: Vec<&str> = result.keys().map(|k| k.as_str()).collect();
+        assert_eq!(keys, vec!["index.html", "index.md", "post"]);
+        Ok(())
+    }
+
+    fn resource(name: &str) -> Resource {
+        Resource {
+            name: name.into(),
+            path: format!("/blog/{}", name),
+        }
+    }
+
+    fn names(content: &Content) -> Vec<&str> {
+        content.resources.iter().map(|r| r.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_assign_resources() {
+        let mut children = vec![content("index.md"), content("post.md")];
+
+        let unclaimed = assign_resources(
+            &mut children,
+            vec![resource("post.png"), resource("photo.jpg")],
+            vec![resource("images/post.png")],
+        );
+
+        assert!(unclaimed.is_empty());
+        assert_eq!(names(&children[0]), vec!["images/post.png", "photo.jpg"]);
+        assert_eq!(names(&children[1]), vec!["post.png"]);
+    }
+
+    #[test]
+    fn test_assign_resources_without_index() {
+        let mut children = vec![content("post.md")];
+
+        let unclaimed = assign_resources(
+            &mut children,
+            vec![resource("post.png"), resource("photo.jpg")],
+            vec![resource("images/post.png")],
+        );
+
+        assert_eq!(names(&children[0]), vec!["post.png"]);
+        let unclaimed: Vec<&str> = unclaimed.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(unclaimed, vec!["photo.jpg", "images/post.png"]);
+    }
+}
@@ -0,0 +1,76 @@
+/// Selects the content files of a single language, by their language suffix (`index.de.md`).
+#[derive(Debug, Clone)]
+pub struct LanguageFilter {
+    /// The language to select.
+    language: String,
+    /// All language codes of the site.
+    codes: Vec<String>,
+    /// Whether files without a language suffix belong to the selected language.
+    unsuffixed: bool,
+}
+
+impl LanguageFilter {
+    pub fn new<S: Into<String>>(language: S, codes: Vec<String>, unsuffixed: bool) -> Self {
+        LanguageFilter {
+            language: language.into(),
+            codes,
+            unsuffixed,
+        }
+    }
+
+    /// The code of the selected language.
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    /// Get the name of a content file, without the language suffix.
+    ///
+    /// Returns `None` if the file belongs to a different language.
+    pub fn name_of(&self, stem: &str) -> Option<String> {
+        let (name, suffix) = self.split(stem);
+        match suffix {
+            Some(suffix) if suffix == self.language => Some(name.into()),
+            Some(_) => None,
+            None if self.unsuffixed => Some(name.into()),
+            None => None,
+        }
+    }
+
+    /// Split a known language suffix from a file stem.
+    fn split<'s>(&self, stem: &'s str) -> (&'s str, Option<&'s str>) {
+        if let Some(pos) = stem.rfind('.') {
+            let (name, suffix) = (&stem[..pos], &stem[pos + 1..]);
+            if !name.is_empty() && self.codes.iter().any(|c| c == suffix) {
+                return (name, Some(suffix));
+            }
+        }
+        (stem, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes() -> Vec<String> {
+        vec!["en".into(), "de".into(), "ja".into()]
+    }
+
+    #[test]
+    fn test_default_language() {
+        let filter = LanguageFilter::new("en", codes(), true);
+        assert_eq!(filter.name_of("index"), Some("index".into()));
+        assert_eq!(filter.name_of("index.en"), Some("index".into()));
+        assert_eq!(filter.name_of("index.de"), None);
+        assert_eq!(filter.name_of("jquery.min"), Some("jquery.min".into()));
+    }
+
+    #[test]
+    fn test_other_language() {
+        let filter = LanguageFilter::new("de", codes(), false);
+        assert_eq!(filter.name_of("index"), None);
+        assert_eq!(filter.name_of("index.de"), Some("index".into()));
+        assert_eq!(filter.name_of("index.ja"), None);
+        assert_eq!(filter.name_of(".de"), None);
+    }
+}
@@ -4,7 +4,7 @@ use std::path::Path;
 
 type Result<T> = std::result::Result<T, Error>;
 
-use crate::loader::{Content, JsonBodyProvider, Loader, Metadata};
+use crate::loader::{content_error, Content, JsonBodyProvider, Loader, Metadata};
 
 use super::front_matter::parse_front_matter;
 
@@ -29,7 +29,8 @@ impl<P1: AsRef<Path> + Debug, P2: AsRef<Path> + Debug> Loader for MarkdownLoader
 
         let data = read_to_string(path)?;
 
-        let front_matter = parse_front_matter(&data)?;
+        // the front matter starts after the first marker line
+        let front_matter = parse_front_matter(&data).map_err(|err| content_error(path, 1, err))?;
 
         Ok(Content {
             metadata: Metadata::from_path(&self.root, path, path.file_stem(), "md"),
@@ -37,6 +38,7 @@ impl<P1: AsRef<Path> + Debug, P2: AsRef<Path> + Debug> Loader for MarkdownLoader
             content: Box::new(JsonBodyProvider::new(serde_json::Value::String(
                 front_matter.0,
             ))),
+            resources: Vec::new(),
         })
     }
 }
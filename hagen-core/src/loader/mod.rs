@@ -7,6 +7,7 @@ use crate::loader::yaml::YAMLLoader;
 
 use serde::{Deserialize, Serialize};
 
+use crate::error::GeneratorError;
 use failure::Error;
 use serde_json::{Map, Value};
 use std::ffi::OsStr;
@@ -14,10 +15,13 @@ use std::fmt::Debug;
 
 type Result<T> = std::result::Result<T, Error>;
 
+pub mod cascade;
 pub mod directory;
 pub mod front_matter;
+pub mod language;
 pub mod markdown;
 pub mod plain;
+pub mod schema;
 pub mod yaml;
 
 pub trait Loader {
@@ -32,6 +36,14 @@ pub struct Metadata {
 
     #[serde(rename = "type")]
     type_name: String,
+
+    /// The language code, on multilingual sites.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language: Option<String>,
+
+    /// Set on untranslated entries, which are taken from the default language.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    fallback: bool,
 }
 
 impl Metadata {
@@ -64,6 +76,8 @@ impl Metadata {
             name: path_to_string(name),
             filename: path_to_string(path.file_name()),
             type_name: type_name.into(),
+            language: None,
+            fallback: false,
         }
     }
 }
@@ -90,6 +104,28 @@ mod tests {
         assert_eq!(m.parent, "/");
     }
 
+    #[test]
+    fn test_content_error_location() {
+        // the '@' is in line 2, column 6 of the front matter, which starts after the '---' line
+        let err = serde_yaml::from_str::<serde_yaml::Value>("title: Foo\nbad: @foo\n").unwrap_err();
+        let err = content_error("index.md", 1, err.into());
+
+        match err.downcast_ref::<GeneratorError>() {
+            Some(GeneratorError::Content { line, column, .. }) => {
+                assert_eq!(*line, Some(3));
+                assert_eq!(*column, Some(6));
+            }
+            _ => panic!("Unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn test_resource() {
+        let r = Resource::from_path(&"/root", &"/root/blog/post/image.png");
+        assert_eq!(r.name, "image.png");
+        assert_eq!(r.path, "/blog/post/image.png");
+    }
+
     #[test]
     fn test_path_root_first() {
         let m = Metadata::from_path(&"/root", &"/root/foo", None, "type");
@@ -97,10 +133,33 @@ mod tests {
     }
 }
 
+/// A file located next to a content file, which is not content itself (like an image).
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Resource {
+    /// The file name.
+    pub name: String,
+    /// The path of the file, relative to the content directory.
+    pub path: String,
+}
+
+impl Resource {
+    pub fn from_path<P1: AsRef<Path>, P2: AsRef<Path>>(root: P1, path: P2) -> Resource {
+        let path = path.as_ref();
+        let relative = path.strip_prefix(root.as_ref()).unwrap_or(path);
+
+        Resource {
+            name: path_to_string(path.file_name()),
+            path: format!("/{}", relative.to_string_lossy().replace('\\', "/")),
+        }
+    }
+}
+
 pub struct Content {
     pub metadata: Metadata,
     pub front_matter: serde_json::Map<String, Value>,
     pub content: Box<dyn BodyProvider>,
+    /// The resources of a page bundle, copied next to the rendered page.
+    pub resources: Vec<Resource>,
 }
 
 impl Content {
@@ -113,6 +172,7 @@ impl Content {
             Value::Object(self.front_matter.clone()),
         );
         m.insert("content".into(), self.content.body()?);
+        m.insert("resources".into(), serde_json::to_value(&self.resources)?);
 
         Ok(Value::Object(m))
     }
@@ -165,6 +225,24 @@ where
     }
 }
 
+/// Wrap an error with the content file it occurred in.
+///
+/// The line offset is the number of lines preceding the YAML document in the file.
+pub(crate) fn content_error<P: AsRef<Path>>(path: P, line_offset: usize, err: Error) -> Error {
+    let location = err
+        .downcast_ref::<serde_yaml::Error>()
+        .and_then(|e| e.location());
+
+    GeneratorError::Content {
+        path: path.as_ref().to_string_lossy().into_owned(),
+        // both are one-based, since serde_yaml 0.8.24
+        line: location.as_ref().map(|l| l.line() + line_offset),
+        column: location.as_ref().map(|l| l.column()),
+        cause: err,
+    }
+    .into()
+}
+
 fn path_to_string<P: AsRef<OsStr>>(path: Option<P>) -> String {
     match path {
         None => String::default(),
@@ -6,7 +6,7 @@ use super::front_matter::parse_front_matter;
 
 type Result<T> = std::result::Result<T, Error>;
 
-use crate::loader::{Content, JsonBodyProvider, Loader, Metadata};
+use crate::loader::{content_error, Content, JsonBodyProvider, Loader, Metadata};
 
 use std::fmt::Debug;
 use std::fs::read_to_string;
@@ -42,7 +42,7 @@ impl<P1: AsRef<Path> + Debug, P2: AsRef<Path> + Debug> Loader for PlainLoader<P1
         let data = read_to_string(path)?;
 
         let front_matter = if self.with_front_matter {
-            parse_front_matter(&data)?
+            parse_front_matter(&data).map_err(|err| content_error(path, 1, err))?
         } else {
             (data, None)
         };
@@ -53,6 +53,7 @@ impl<P1: AsRef<Path> + Debug, P2: AsRef<Path> + Debug> Loader for PlainLoader<P1
             content: Box::new(JsonBodyProvider::new(serde_json::Value::String(
                 front_matter.0,
             ))),
+            resources: Vec::new(),
         })
     }
 }
@@ -0,0 +1,214 @@
+use failure::Error;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use jsonschema::JSONSchema;
+use log::info;
+use serde_json::{Map, Value};
+
+use crate::error::GeneratorError;
+use crate::rules::Schemas;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Validates the front matter of content entries against JSON schemas.
+pub struct SchemaValidator {
+    layouts: BTreeMap<String, JSONSchema>,
+    types: BTreeMap<String, JSONSchema>,
+}
+
+impl SchemaValidator {
+    /// Load and compile all configured schemas, relative to the root.
+    pub fn load<P: AsRef<Path>>(root: P, schemas: &Schemas) -> Result<Self> {
+        let root = root.as_ref();
+
+        Ok(SchemaValidator {
+            layouts: compile_all(root, &schemas.layouts)?,
+            types: compile_all(root, &schemas.types)?,
+        })
+    }
+
+    /// Validate all entries of a content tree, returning one error for each invalid entry.
+    ///
+    /// The content directory is used to report the path of the content file.
+    pub fn validate<P: AsRef<Path>>(&self, content_dir: P, content: &Value) -> Vec<GeneratorError> {
+        let mut errors = Vec::new();
+
+        if !self.layouts.is_empty() || !self.types.is_empty() {
+            self.validate_entry(content_dir.as_ref(), content, &mut errors);
+        }
+
+        errors
+    }
+
+    fn validate_entry(&self, dir: &Path, entry: &Value, errors: &mut Vec<GeneratorError>) {
+        if let Some(schema) = self.schema_for(entry) {
+            let front_matter = entry
+                .get("frontMatter")
+                .cloned()
+                .unwrap_or_else(|| Value::Object(Map::new()));
+
+            let result = schema.validate(&front_matter);
+            if let Err(failures) = result {
+                let failures: Vec<String> = failures
+                    .map(|f| format!("{}: {}", pointer(f.instance_path.to_string()), f))
+                    .collect();
+
+                errors.push(GeneratorError::Content {
+                    path: content_file(dir, entry).to_string_lossy().into_owned(),
+                    line: None,
+                    column: None,
+                    cause: GeneratorError::Error(format!(
+                        "Front matter doesn't match schema: {}",
+                        failures.join("; ")
+                    ))
+                    .into(),
+                });
+            }
+        }
+
+        // descend into directories
+
+        if entry.pointer("/metadata/type").and_then(|v| v.as_str()) == Some("directory") {
+            if let Some(Value::Object(children)) = entry.get("content") {
+                for child in children.values() {
+                    self.validate_entry(dir, child, errors);
+                }
+            }
+        }
+    }
+
+    fn schema_for(&self, entry: &Value) -> Option<&JSONSchema> {
+        let layout = entry
+            .pointer("/frontMatter/layout")
+            .and_then(|v| v.as_str())
+            .and_then(|l| self.layouts.get(l));
+
+        layout.or_else(|| {
+            entry
+                .pointer("/metadata/type")
+                .and_then(|v| v.as_str())
+                .and_then(|t| self.types.get(t))
+        })
+    }
+}
+
+fn compile_all(
+    root: &Path,
+    schemas: &BTreeMap<String, String>,
+) -> Result<BTreeMap<String, JSONSchema>> {
+    let mut result = BTreeMap::new();
+
+    for (k, v) in schemas {
+        let path = root.join(v);
+        info!("Loading schema: {} -> {:?}", k, path);
+
+        let schema: Value = serde_yaml::from_reader(File::open(&path)?)?;
+        let schema = compile(&schema)
+            .map_err(|err| GeneratorError::Error(format!("Invalid schema {:?}: {}", path, err)))?;
+
+        result.insert(k.clone(), schema);
+    }
+
+    Ok(result)
+}
+
+fn compile(schema: &Value) -> std::result::Result<JSONSchema, String> {
+    JSONSchema::compile(schema).map_err(|err| err.to_string())
+}
+
+/// The JSON pointer of the failing value, the empty pointer refers to the whole front matter.
+fn pointer(pointer: String) -> String {
+    if pointer.is_empty() {
+        "/".into()
+    } else {
+        pointer
+    }
+}
+
+/// Get the path of the content file of an entry.
+fn content_file(dir: &Path, entry: &Value) -> PathBuf {
+    let parent = entry
+        .pointer("/metadata/parent")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+    let filename = entry
+        .pointer("/metadata/filename")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+
+    dir.join(parent.trim_start_matches('/')).join(filename)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn validator() -> SchemaValidator {
+        let schema = json!({
+            "type": "object",
+            "required": ["title"],
+            "properties": {
+                "timestamp": {
+                    "type": "object",
+                    "properties": { "published": { "type": "string" } },
+                    "additionalProperties": false
+                }
+            }
+        });
+
+        let mut layouts = BTreeMap::new();
+        layouts.insert("post".to_string(), compile(&schema).unwrap());
+
+        SchemaValidator {
+            layouts,
+            types: BTreeMap::new(),
+        }
+    }
+
+    fn entry(front_matter: Value) -> Value {
+        json!({
+            "metadata": { "parent": "/blog", "filename": "post.md", "type": "md" },
+            "frontMatter": front_matter,
+            "content": "",
+        })
+    }
+
+    #[test]
+    fn test_valid() {
+        let content = entry(json!({"layout": "post", "title": "Foo"}));
+        assert!(validator().validate("/content", &content).is_empty());
+    }
+
+    #[test]
+    fn test_no_schema() {
+        let content = entry(json!({"layout": "page"}));
+        assert!(validator().validate("/content", &content).is_empty());
+    }
+
+    #[test]
+    fn test_invalid() {
+        let content = json!({
+            "metadata": { "parent": "/", "filename": "blog", "type": "directory" },
+            "frontMatter": {},
+            "content": {
+                "post": entry(json!({"layout": "post", "timestamp": {"publisehd": "2020-01-01"}})),
+            },
+        });
+
+        let errors = validator().validate("/content", &content);
+        assert_eq!(errors.len(), 1);
+
+        match &errors[0] {
+            GeneratorError::Content { path, cause, .. } => {
+                assert_eq!(path, "/content/blog/post.md");
+                let message = cause.to_string();
+                assert!(message.contains("/timestamp"), "{}", message);
+                assert!(message.contains("title"), "{}", message);
+            }
+            err => panic!("Unexpected error: {}", err),
+        }
+    }
+}
@@ -4,7 +4,7 @@ use std::path::Path;
 
 type Result<T> = std::result::Result<T, Error>;
 
-use crate::loader::{Content, JsonBodyProvider, Loader, Metadata};
+use crate::loader::{content_error, Content, JsonBodyProvider, Loader, Metadata};
 
 use serde_json::{Map, Value};
 use std::fmt::Debug;
@@ -27,12 +27,14 @@ impl<P1: AsRef<Path> + Debug, P2: AsRef<Path> + Debug> Loader for YAMLLoader<P1,
         info!("Loading - YAML: {:?}", path);
 
         let reader = File::open(path)?;
-        let content: Value = serde_yaml::from_reader(reader)?;
+        let content: Value =
+            serde_yaml::from_reader(reader).map_err(|err| content_error(path, 0, err.into()))?;
 
         Ok(Content {
             metadata: Metadata::from_path(&self.root, path, path.file_stem(), "yaml"),
             front_matter: Map::new(),
             content: Box::new(JsonBodyProvider::new(content)),
+            resources: Vec::new(),
         })
     }
 }
@@ -0,0 +1,219 @@
+use failure::Error;
+use std::fs;
+use std::path::Path;
+
+use log::{debug, info};
+use quick_xml::events::Event;
+use quick_xml::{Reader, Writer};
+
+use crate::error::GeneratorError;
+use crate::rules::Bundle;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Types of files, which can be minified.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Css,
+    Js,
+}
+
+impl Kind {
+    /// Get the kind of a file, from its extension.
+    pub fn of<P: AsRef<Path>>(path: P) -> Option<Kind> {
+        match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some("css") => Some(Kind::Css),
+            Some("js") => Some(Kind::Js),
+            _ => None,
+        }
+    }
+
+    /// The separator, when concatenating files.
+    fn separator(&self) -> &'static str {
+        match self {
+            Kind::Css => "\n",
+            Kind::Js => ";\n",
+        }
+    }
+}
+
+/// Minify the content of a file.
+pub fn minify(kind: Kind, source: &str) -> Result<String> {
+    match kind {
+        Kind::Css => Ok(minifier::css::minify(source)
+            .map_err(|err| GeneratorError::Error(format!("Failed to minify CSS: {}", err)))?
+            .to_string()),
+        Kind::Js => Ok(minifier::js::minify(source).to_string()),
+    }
+}
+
+/// Minify a file in place. Files which are already minified (`.min.css`, `.min.js`) are skipped.
+pub fn minify_file<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref();
+
+    let kind = match Kind::of(path) {
+        Some(kind) if !is_minified(path) => kind,
+        _ => return Ok(()),
+    };
+
+    debug!("Minify: {:?}", path);
+
+    let source = fs::read_to_string(path)?;
+    let result = minify(kind, &source)
+        .map_err(|err| GeneratorError::GenericDetailError(err, format!("{:?}", path)))?;
+    fs::write(path, result)?;
+
+    Ok(())
+}
+
+/// Concatenate the files of a bundle, relative to the source directory, into a single file
+/// in the target directory.
+pub fn write_bundle<P1, P2>(from: P1, to: P2, bundle: &Bundle, minify: bool) -> Result<()>
+where
+    P1: AsRef<Path>,
+    P2: AsRef<Path>,
+{
+    let target = to.as_ref().join(&bundle.output);
+
+    let kind = Kind::of(&target).ok_or_else(|| {
+        GeneratorError::Error(format!(
+            "Bundle must be a '.css' or '.js' file: {}",
+            bundle.output
+        ))
+    })?;
+
+    info!("Bundling {} files: {:?}", bundle.files.len(), target);
+
+    let mut content = String::new();
+    for f in &bundle.files {
+        content.push_str(&fs::read_to_string(from.as_ref().join(f))?);
+        content.push_str(kind.separator());
+    }
+
+    if minify {
+        content = self::minify(kind, &content)?;
+    }
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(target, content)?;
+
+    Ok(())
+}
+
+/// Check if a rendered page is HTML, and can be minified.
+pub fn is_html(path: &str) -> bool {
+    path.ends_with(".html") || path.ends_with(".htm")
+}
+
+/// Minify an HTML document.
+///
+/// Collapses whitespace, removes comments and optional tags. The content of `pre`, `textarea`,
+/// as well as inline scripts and styles are kept as they are.
+pub fn minify_html(source: &[u8]) -> Vec<u8> {
+    let mut cfg = ::minify_html::Cfg::new();
+    cfg.do_not_minify_doctype = true;
+    cfg.ensure_spec_compliant_unquoted_attribute_values = true;
+    cfg.keep_spaces_between_attributes = true;
+    cfg.keep_comments = false;
+    cfg.keep_closing_tags = false;
+    cfg.keep_html_and_head_opening_tags = false;
+    cfg.minify_css = false;
+    cfg.minify_js = false;
+
+    ::minify_html::minify(source, &cfg)
+}
+
+/// Minify an XML document, by removing the whitespace between elements.
+pub fn minify_xml(source: &str) -> Result<String> {
+    let mut reader = Reader::from_str(source);
+    let mut writer = Writer::new(Vec::new());
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Eof => break,
+            Event::Text(ref text) if text.escaped().iter().all(u8::is_ascii_whitespace) => {}
+            event => {
+                writer.write_event(event)?;
+            }
+        }
+        buf.clear();
+    }
+
+    Ok(String::from_utf8(writer.into_inner())?)
+}
+
+/// Minify an XML file in place.
+pub fn minify_xml_file<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref();
+    debug!("Minify: {:?}", path);
+
+    let result = minify_xml(&fs::read_to_string(path)?)
+        .map_err(|err| GeneratorError::GenericDetailError(err, format!("{:?}", path)))?;
+    fs::write(path, result)?;
+
+    Ok(())
+}
+
+fn is_minified(path: &Path) -> bool {
+    path.file_stem()
+        .and_then(|s| s.to_str())
+        .map(|s| s.ends_with(".min"))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kind() {
+        assert_eq!(Kind::of("foo/bar.css"), Some(Kind::Css));
+        assert_eq!(Kind::of("foo/bar.min.js"), Some(Kind::Js));
+        assert_eq!(Kind::of("foo/bar.scss"), None);
+    }
+
+    #[test]
+    fn test_is_minified() {
+        assert!(is_minified(Path::new("jquery.min.js")));
+        assert!(!is_minified(Path::new("jquery.js")));
+    }
+
+    #[test]
+    fn test_minify_css() -> Result<()> {
+        let result = minify(Kind::Css, "body {\n    color: red;\n}\n")?;
+        assert!(result.starts_with("body{color:red"), "{}", result);
+        assert!(!result.contains('\n'), "{}", result);
+        Ok(())
+    }
+
+    #[test]
+    fn test_minify_html() {
+        let source = "<html>\n  <body>\n    <!-- comment -->\n    <p>Hello   World</p>\n    <pre>  keep\n  this</pre>\n    <script>var  x = 1;</script>\n  </body>\n</html>\n";
+        let result = String::from_utf8(minify_html(source.as_bytes())).unwrap();
+        assert!(!result.contains("comment"), "{}", result);
+        assert!(!result.contains("  <p>"), "{}", result);
+        assert!(result.contains("<pre>  keep\n  this</pre>"), "{}", result);
+        assert!(result.contains("var  x = 1;"), "{}", result);
+    }
+
+    #[test]
+    fn test_minify_xml() -> Result<()> {
+        let source = "<?xml version=\"1.0\"?>\n<urlset>\n\t<url>\n\t\t<loc>https://example.com/a b</loc>\n\t</url>\n</urlset>\n";
+        let result = minify_xml(source)?;
+        assert_eq!(
+            result,
+            "<?xml version=\"1.0\"?><urlset><url><loc>https://example.com/a b</loc></url></urlset>"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_minify_js() -> Result<()> {
+        let result = minify(Kind::Js, "// comment\nvar x = 1;\n")?;
+        assert!(!result.contains("comment"), "{}", result);
+        Ok(())
+    }
+}
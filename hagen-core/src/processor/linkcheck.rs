@@ -0,0 +1,294 @@
+use crate::generator::{GeneratorConfig, Output};
+use crate::processor::{Processor, ProcessorContext};
+use failure::Error;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::engine::TemplateEngine;
+use crate::error::GeneratorError;
+use lazy_static::lazy_static;
+use percent_encoding::percent_decode_str;
+use regex::Regex;
+use relative_path::RelativePath;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use url::Url;
+use walkdir::WalkDir;
+
+use log::{info, warn};
+
+type Result<T> = std::result::Result<T, Error>;
+
+lazy_static! {
+    static ref RE_LINK: Regex =
+        Regex::new(r#"(?i)\b(?:href|src)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap();
+    static ref RE_ID: Regex =
+        Regex::new(r#"(?i)\b(?:id|name)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap();
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+struct LinkCheckProcessorConfig {
+    /// Fail the build if broken links are found.
+    strict: bool,
+    /// Check `#fragment` parts of links against the ids of the target page.
+    check_fragments: Option<bool>,
+}
+
+/// A broken link, found in a generated page.
+#[derive(Debug, Clone)]
+struct BrokenLink {
+    source: String,
+    line: usize,
+    link: String,
+    reason: String,
+}
+
+pub struct LinkCheckProcessor;
+
+impl Processor for LinkCheckProcessor {
+    fn create<'a>(
+        &self,
+        _: &mut dyn TemplateEngine,
+        _: &Value,
+        generator_config: &'a GeneratorConfig,
+        processor_config: Value,
+    ) -> Result<Box<dyn ProcessorContext + 'a>> {
+        let config = serde_json::from_value(processor_config)?;
+
+        Ok(Box::new(LinkCheckContext {
+            config,
+            generator_config,
+            ids: HashMap::new(),
+        }))
+    }
+}
+
+pub struct LinkCheckContext<'a> {
+    config: LinkCheckProcessorConfig,
+    generator_config: &'a GeneratorConfig,
+    // cache of fragment ids per output file
+    ids: HashMap<PathBuf, HashSet<String>>,
+}
+
+impl<'a> LinkCheckContext<'a> {
+    fn check_file(&mut self, path: &Path, result: &mut Vec<BrokenLink>) -> Result<()> {
+        let output = &self.generator_config.output;
+        let relative = path
+            .strip_prefix(output)?
+            .to_str()
+            .ok_or_else(|| GeneratorError::Error(format!("Invalid file name: {:?}", path)))?
+            .to_string();
+
+        let page = Output::new(
+            self.generator_config.basename.as_str(),
+            &relative,
+            Option::None::<String>,
+        )?;
+        let page_url = Url::parse(&page.url)?;
+
+        let data = fs::read_to_string(path)?;
+
+        for (n, line) in data.lines().enumerate() {
+            for cap in RE_LINK.captures_iter(line) {
+                let link = cap
+                    .get(1)
+                    .or_else(|| cap.get(2))
+                    .map(|m| m.as_str())
+                    .unwrap_or_default();
+
+                if let Some(reason) = self.check_link(&page_url, link)? {
+                    result.push(BrokenLink {
+                        source: relative.clone(),
+                        line: n + 1,
+                        link: link.into(),
+                        reason,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check a single link, returning the reason why it is broken, or `None` if it is fine.
+    fn check_link(&mut self, page_url: &Url, link: &str) -> Result<Option<String>> {
+        let link = html_unescape(link.trim());
+
+        if link.is_empty() || link.starts_with("data:") {
+            return Ok(None);
+        }
+
+        let target = match page_url.join(&link) {
+            Ok(url) => url,
+            Err(err) => return Ok(Some(format!("Unable to parse URL: {}", err))),
+        };
+
+        // only check links pointing into our own site
+
+        let path = match site_path(&self.generator_config.basename, &target) {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let path = percent_decode_str(&path).decode_utf8()?;
+
+        let mut file = RelativePath::new(path.as_ref()).to_path(&self.generator_config.output);
+        if file.is_dir() {
+            file = file.join("index.html");
+        }
+
+        if !file.is_file() {
+            return Ok(Some("Target does not exist".into()));
+        }
+
+        if let Some(fragment) = target.fragment() {
+            if fragment.is_empty() || !self.config.check_fragments.unwrap_or(true) {
+                return Ok(None);
+            }
+            let fragment = percent_decode_str(fragment).decode_utf8()?;
+            if !self.ids_for(&file)?.contains(fragment.as_ref()) {
+                return Ok(Some(format!("Fragment '#{}' does not exist", fragment)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn ids_for(&mut self, file: &Path) -> Result<&HashSet<String>> {
+        if !self.ids.contains_key(file) {
+            let mut ids = HashSet::new();
+            if is_html(file) {
+                let data = fs::read_to_string(file)?;
+                for cap in RE_ID.captures_iter(&data) {
+                    if let Some(id) = cap.get(1).or_else(|| cap.get(2)) {
+                        ids.insert(html_unescape(id.as_str()));
+                    }
+                }
+            }
+            self.ids.insert(file.to_path_buf(), ids);
+        }
+
+        Ok(&self.ids[file])
+    }
+}
+
+impl<'a> ProcessorContext for LinkCheckContext<'a> {
+    fn file_created(&mut self, _: &Output, _: &Value, _: &mut dyn TemplateEngine) -> Result<()> {
+        Ok(())
+    }
+
+    fn complete(&mut self, _: &mut dyn TemplateEngine) -> Result<()> {
+        info!("Checking links");
+
+        let mut result = Vec::new();
+
+        let files: Vec<PathBuf> = WalkDir::new(&self.generator_config.output)
+            .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .map(|e| e.into_path())
+            .filter(|p| p.is_file() && is_html(p))
+            .collect();
+
+        for file in &files {
+            self.check_file(file, &mut result)?;
+        }
+
+        for broken in &result {
+            warn!(
+                "Broken link: {}:{}: '{}' - {}",
+                broken.source, broken.line, broken.link, broken.reason
+            );
+        }
+
+        info!(
+            "Checked {} files, found {} broken links",
+            files.len(),
+            result.len()
+        );
+
+        if self.config.strict && !result.is_empty() {
+            return Err(
+                GeneratorError::Error(format!("Found {} broken links", result.len())).into(),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn is_html(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("html") | Some("htm")
+    )
+}
+
+fn html_unescape(s: &str) -> String {
+    s.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Get the path of `target` relative to the site `base`, or `None` if it points outside the site.
+///
+/// Compares origin and path segments, so `https://example.com/blog` is not considered part of
+/// `https://example.com/blog-old/`.
+fn site_path(base: &Url, target: &Url) -> Option<String> {
+    if base.origin() != target.origin() {
+        return None;
+    }
+
+    let base: Vec<_> = base
+        .path_segments()?
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    let target: Vec<_> = target.path_segments()?.collect();
+
+    if target.len() < base.len() || target[..base.len()] != base[..] {
+        return None;
+    }
+
+    Some(target[base.len()..].join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_links() {
+        let links: Vec<_> = RE_LINK
+            .captures_iter(r#"<a href="/foo">Foo</a> <img src='bar.png'>"#)
+            .map(|c| c.get(1).or_else(|| c.get(2)).unwrap().as_str())
+            .collect();
+        assert_eq!(links, vec!["/foo", "bar.png"]);
+    }
+
+    #[test]
+    fn test_site_path() {
+        let base = Url::parse("https://example.com/blog/").unwrap();
+        let path = |url: &str| site_path(&base, &Url::parse(url).unwrap());
+
+        assert_eq!(path("https://example.com/blog/"), Some("".into()));
+        assert_eq!(path("https://example.com/blog"), Some("".into()));
+        assert_eq!(
+            path("https://example.com/blog/a/b.html"),
+            Some("a/b.html".into())
+        );
+        assert_eq!(path("https://example.com/blog-old/a.html"), None);
+        assert_eq!(path("https://example.com:8443/blog/a.html"), None);
+        assert_eq!(path("http://example.com/blog/a.html"), None);
+
+        let root = Url::parse("https://example.com").unwrap();
+        assert_eq!(
+            site_path(&root, &Url::parse("https://example.com/a/").unwrap()),
+            Some("a/".into())
+        );
+    }
+}
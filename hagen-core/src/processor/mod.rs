@@ -1,14 +1,16 @@
+use crate::engine::TemplateEngine;
 use crate::generator::{GeneratorConfig, Output};
 use crate::path::first_value_for_path;
 use failure::Error;
-use handlebars::Handlebars;
 use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
 use quick_xml::Writer;
 use serde_json::{Map, Value};
 use std::collections::HashMap;
 use std::io::Write;
 
+pub mod linkcheck;
 pub mod rss;
+pub mod search;
 pub mod sitemap;
 
 type Result<T> = std::result::Result<T, Error>;
@@ -16,7 +18,7 @@ type Result<T> = std::result::Result<T, Error>;
 pub trait Processor {
     fn create<'a, 'reg>(
         &self,
-        handlebars: &'reg mut Handlebars,
+        engine: &'reg mut dyn TemplateEngine,
         data: &Value,
         config: &'a GeneratorConfig,
         processor_config: Value,
@@ -28,29 +30,35 @@ pub trait ProcessorContext {
         &mut self,
         output: &Output,
         context: &Value,
-        handlebars: &mut Handlebars,
+        engine: &mut dyn TemplateEngine,
     ) -> Result<()>;
-    fn complete(&mut self, handlebars: &mut Handlebars) -> Result<()>;
+    fn complete(&mut self, engine: &mut dyn TemplateEngine) -> Result<()>;
+
+    /// The files written by the processor, relative to the output directory.
+    fn outputs(&self) -> Vec<String> {
+        Vec::new()
+    }
 }
 
 pub struct ProcessorSession<'a> {
-    processors: Vec<Box<dyn ProcessorContext + 'a>>,
+    processors: Vec<(String, Box<dyn ProcessorContext + 'a>)>,
 }
 
 impl<'a> ProcessorSession<'a> {
     pub fn new<'reg>(
         processors: &HashMap<String, Box<dyn Processor + 'a>>,
-        handlebars: &'reg mut Handlebars,
+        engine: &'reg mut dyn TemplateEngine,
         data: &Value,
         config: &'a GeneratorConfig,
         processor_configs: &Map<String, Value>,
     ) -> Result<ProcessorSession<'a>> {
-        let processors: Result<Vec<Box<dyn ProcessorContext + 'a>>> = processors
+        let processors: Result<Vec<(String, Box<dyn ProcessorContext + 'a>)>> = processors
             .into_iter()
             .map(|(k, p)| {
-                processor_configs
-                    .get(k)
-                    .map(|c| p.create(handlebars, data, config, c.clone()))
+                processor_configs.get(k).map(|c| {
+                    p.create(engine, data, config, c.clone())
+                        .map(|p| (k.clone(), p))
+                })
             })
             .filter_map(|o| o)
             .collect();
@@ -63,20 +71,28 @@ impl<'a> ProcessorSession<'a> {
         &mut self,
         output: &Output,
         context: &Value,
-        handlebars: &mut Handlebars,
+        engine: &mut dyn TemplateEngine,
     ) -> Result<()> {
-        for p in &mut self.processors {
-            (*p).file_created(output, context, handlebars)?;
+        for (_, p) in &mut self.processors {
+            (*p).file_created(output, context, engine)?;
         }
         Ok(())
     }
 
-    pub fn complete(&mut self, handlebars: &mut Handlebars) -> Result<()> {
-        for p in &mut self.processors {
-            (*p).complete(handlebars)?;
+    pub fn complete(&mut self, engine: &mut dyn TemplateEngine) -> Result<()> {
+        for (_, p) in &mut self.processors {
+            (*p).complete(engine)?;
         }
         Ok(())
     }
+
+    /// The files written by the processors, along with the name of the processor.
+    pub fn outputs(&self) -> Vec<(String, String)> {
+        self.processors
+            .iter()
+            .flat_map(|(name, p)| p.outputs().into_iter().map(move |o| (name.clone(), o)))
+            .collect()
+    }
 }
 
 pub fn xml_write_element<'a, S1, S2, W>(writer: &mut Writer<W>, name: S1, value: S2) -> Result<()>
@@ -9,10 +9,12 @@ use quick_xml::Writer;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::engine::TemplateEngine;
 use crate::error::GeneratorError;
-use handlebars::Handlebars;
+use crate::minify;
 use std::fs::File;
 use std::io::Write;
+use std::path::PathBuf;
 
 type Result<T> = std::result::Result<T, Error>;
 
@@ -29,6 +31,7 @@ struct RssProcessorConfig {
 #[serde(default)]
 struct Site {
     pub title: Option<String>,
+    /// The language of the feed, and of the pages it covers on multilingual sites.
     pub language: Option<String>,
     pub description: Option<String>,
     pub update_period: String,
@@ -71,6 +74,10 @@ impl Default for Site {
     }
 }
 
+/// Writes a single RSS feed, `feed.rss`.
+///
+/// On multilingual sites, the feed only covers the pages of one language: the default language,
+/// unless `site.language` selects a different one. Other languages get no feed.
 pub struct RssProcessor;
 
 impl RssProcessor {}
@@ -78,14 +85,15 @@ impl RssProcessor {}
 impl Processor for RssProcessor {
     fn create<'a>(
         &self,
-        handlebars: &mut Handlebars,
+        engine: &mut dyn TemplateEngine,
         data: &Value,
         generator_config: &'a GeneratorConfig,
         processor_config: Value,
     ) -> Result<Box<dyn ProcessorContext + 'a>> {
         let config: RssProcessorConfig = serde_json::from_value(processor_config)?;
 
-        let writer = File::create(generator_config.output.join("feed.rss"))?;
+        let target = generator_config.output.join("feed.rss");
+        let writer = File::create(&target)?;
         let mut writer = Writer::new(writer);
 
         writer.write_event(Event::Decl(BytesDecl::new(b"1.0", Some(b"UTF-8"), None)))?;
@@ -142,15 +150,20 @@ impl Processor for RssProcessor {
         // site
 
         if let Some(ref title) = config.site.title {
-            let title = handlebars.render_template(title.as_str(), data)?;
+            let title = engine.render_template(title.as_str(), data)?;
             xml_write_element(&mut writer, "title", title)?;
         }
-        if let Some(ref language) = config.site.language {
-            let title = handlebars.render_template(language.as_str(), data)?;
-            xml_write_element(&mut writer, "language", title)?;
+        // on multilingual sites, the feed defaults to the default language
+
+        let language = match config.site.language {
+            Some(ref language) => Some(engine.render_template(language.as_str(), data)?),
+            None => generator_config.languages.first().map(|l| l.code.clone()),
+        };
+        if let Some(ref language) = language {
+            xml_write_element(&mut writer, "language", language)?;
         }
         if let Some(ref description) = config.site.description {
-            let title = handlebars.render_template(description.as_str(), data)?;
+            let title = engine.render_template(description.as_str(), data)?;
             xml_write_element(&mut writer, "description", title)?;
         }
 
@@ -166,13 +179,28 @@ impl Processor for RssProcessor {
             xml_write_element(&mut writer, "sy:updateBase", update_base)?;
         }
 
-        Ok(Box::new(RssContext { config, writer }))
+        let minify = if generator_config.minify.xml {
+            Some(target)
+        } else {
+            None
+        };
+
+        Ok(Box::new(RssContext {
+            config,
+            writer,
+            language,
+            minify,
+        }))
     }
 }
 
 pub struct RssContext<W: Write> {
     config: RssProcessorConfig,
     writer: Writer<W>,
+    /// The language of the feed, pages in other languages are skipped.
+    language: Option<String>,
+    /// The file to minify, once complete.
+    minify: Option<PathBuf>,
 }
 
 impl<W: Write> RssContext<W> {
@@ -188,7 +216,7 @@ impl<W: Write> RssContext<W> {
 
     fn eval_value<F>(
         &self,
-        handlebars: &Handlebars,
+        engine: &mut dyn TemplateEngine,
         context: &Value,
         page_data: &Data,
         f: F,
@@ -211,7 +239,7 @@ impl<W: Write> RssContext<W> {
 
         let expr = expr.unwrap();
 
-        let result = handlebars.render_template(&expr, context)?;
+        let result = engine.render_template(&expr, context)?;
         if result.is_empty() {
             Ok(None)
         } else {
@@ -225,8 +253,14 @@ impl<W: Write> ProcessorContext for RssContext<W> {
         &mut self,
         output: &Output,
         context: &Value,
-        handlebars: &mut Handlebars,
+        engine: &mut dyn TemplateEngine,
     ) -> Result<()> {
+        if let (Some(feed), Some(page)) = (&self.language, &output.language) {
+            if feed != page {
+                return Ok(());
+            }
+        }
+
         let m = self.matches(context)?;
         if m.is_none() {
             return Ok(());
@@ -237,22 +271,22 @@ impl<W: Write> ProcessorContext for RssContext<W> {
         // gather information
 
         let title = self
-            .eval_value(handlebars, context, &m.data, |d| &d.title)?
+            .eval_value(engine, context, &m.data, |d| &d.title)?
             .ok_or_else(|| {
                 GeneratorError::Error(format!(
                     "Missing value for 'title' for RSS in page '{:?}'",
                     output.path
                 ))
             })?;
-        let creator = self.eval_value(handlebars, context, &m.data, |d| &d.creator)?;
-        let author = self.eval_value(handlebars, context, &m.data, |d| &d.author)?;
+        let creator = self.eval_value(engine, context, &m.data, |d| &d.creator)?;
+        let author = self.eval_value(engine, context, &m.data, |d| &d.author)?;
         let pub_date = self
-            .eval_value(handlebars, context, &m.data, |d| &d.published)?
+            .eval_value(engine, context, &m.data, |d| &d.published)?
             .map(|s| DateTime::parse_from_rfc3339(&s))
             .transpose()?
             .map(|d| d.with_timezone(&Utc));
-        let description = self.eval_value(handlebars, context, &m.data, |d| &d.description)?;
-        let content = self.eval_value(handlebars, context, &m.data, |d| &d.content)?;
+        let description = self.eval_value(engine, context, &m.data, |d| &d.description)?;
+        let content = self.eval_value(engine, context, &m.data, |d| &d.content)?;
 
         // item
 
@@ -320,7 +354,11 @@ impl<W: Write> ProcessorContext for RssContext<W> {
         Ok(())
     }
 
-    fn complete(&mut self, _: &mut Handlebars) -> Result<()> {
+    fn outputs(&self) -> Vec<String> {
+        vec!["feed.rss".into()]
+    }
+
+    fn complete(&mut self, _: &mut dyn TemplateEngine) -> Result<()> {
         self.writer
             .write_event(Event::End(BytesEnd::borrowed(b"channel")))?;
         self.writer.write(b"\n")?;
@@ -328,6 +366,11 @@ impl<W: Write> ProcessorContext for RssContext<W> {
         self.writer
             .write_event(Event::End(BytesEnd::borrowed(b"rss")))?;
         self.writer.write(b"\n")?;
+        self.writer.inner().flush()?;
+
+        if let Some(ref target) = self.minify {
+            minify::minify_xml_file(target)?;
+        }
 
         Ok(())
     }
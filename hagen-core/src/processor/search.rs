@@ -0,0 +1,330 @@
+use crate::generator::{GeneratorConfig, Output};
+use crate::processor::{Having, Processor, ProcessorContext};
+use failure::Error;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::engine::TemplateEngine;
+use crate::error::GeneratorError;
+use lazy_static::lazy_static;
+use regex::Regex;
+use rust_stemmers::{Algorithm, Stemmer};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::PathBuf;
+
+use log::info;
+
+type Result<T> = std::result::Result<T, Error>;
+
+lazy_static! {
+    static ref RE_SCRIPT: Regex = Regex::new(r"(?is)<script[^>]*>.*?</script>").unwrap();
+    static ref RE_STYLE: Regex = Regex::new(r"(?is)<style[^>]*>.*?</style>").unwrap();
+    static ref RE_TAG: Regex = Regex::new(r"(?s)<[^>]*>").unwrap();
+    static ref RE_WS: Regex = Regex::new(r"\s+").unwrap();
+    static ref RE_WORD: Regex = Regex::new(r"\w+").unwrap();
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct SearchProcessorConfig {
+    /// The name of the index file, relative to the output directory.
+    #[serde(default = "default_output")]
+    output: String,
+    pages: Vec<Page>,
+    #[serde(default)]
+    defaults: Data,
+    /// Pre-build an inverted index (term to document) as part of the index file.
+    #[serde(default)]
+    inverted_index: bool,
+    /// The stemming language to use for the inverted index, e.g. "english".
+    stemming: Option<String>,
+    /// The maximum number of characters of text to store per page.
+    max_text_length: Option<usize>,
+}
+
+fn default_output() -> String {
+    "search.json".into()
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+struct Data {
+    pub title: Option<String>,
+    pub section: Option<String>,
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct Page {
+    #[serde(default)]
+    pub data: Data,
+    pub having: Having,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct Document {
+    url: String,
+    title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    section: Option<String>,
+    text: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Index<'a> {
+    documents: &'a [Document],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    terms: Option<BTreeMap<String, Vec<usize>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stemming: Option<&'a str>,
+}
+
+pub struct SearchProcessor;
+
+impl Processor for SearchProcessor {
+    fn create<'a>(
+        &self,
+        _: &mut dyn TemplateEngine,
+        _: &Value,
+        generator_config: &'a GeneratorConfig,
+        processor_config: Value,
+    ) -> Result<Box<dyn ProcessorContext + 'a>> {
+        let config: SearchProcessorConfig = serde_json::from_value(processor_config)?;
+
+        let stemmer = config
+            .stemming
+            .as_ref()
+            .map(|s| algorithm_for(s).map(Stemmer::create))
+            .transpose()?;
+
+        Ok(Box::new(SearchContext {
+            target: generator_config.output.join(&config.output),
+            config,
+            stemmer,
+            documents: Vec::new(),
+        }))
+    }
+}
+
+pub struct SearchContext {
+    config: SearchProcessorConfig,
+    target: PathBuf,
+    stemmer: Option<Stemmer>,
+    documents: Vec<Document>,
+}
+
+impl SearchContext {
+    fn matches(&self, context: &Value) -> Result<Option<&Page>> {
+        for p in &self.config.pages {
+            if p.having.matches(context)? {
+                return Ok(Some(p));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn eval_value<F>(
+        &self,
+        engine: &mut dyn TemplateEngine,
+        context: &Value,
+        page_data: &Data,
+        f: F,
+    ) -> Result<Option<String>>
+    where
+        F: Fn(&Data) -> &Option<String>,
+    {
+        let expr = match (f(page_data), f(&self.config.defaults)) {
+            (Some(x), _) => x,
+            (None, Some(x)) => x,
+            (None, None) => return Ok(None),
+        };
+
+        let result = engine.render_template(&expr, context)?;
+        let result = result.trim();
+        if result.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(result.into()))
+        }
+    }
+
+    /// Build the inverted index, mapping each (stemmed) term to the documents containing it.
+    fn build_terms(&self) -> BTreeMap<String, Vec<usize>> {
+        let mut terms: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+
+        for (i, doc) in self.documents.iter().enumerate() {
+            let text = format!("{} {}", doc.title, doc.text);
+            for word in RE_WORD.find_iter(&text) {
+                let word = word.as_str().to_lowercase();
+                let term = match self.stemmer {
+                    Some(ref stemmer) => stemmer.stem(&word).into_owned(),
+                    None => word,
+                };
+
+                let docs = terms.entry(term).or_default();
+                if docs.last() != Some(&i) {
+                    docs.push(i);
+                }
+            }
+        }
+
+        terms
+    }
+}
+
+impl ProcessorContext for SearchContext {
+    fn file_created(
+        &mut self,
+        output: &Output,
+        context: &Value,
+        engine: &mut dyn TemplateEngine,
+    ) -> Result<()> {
+        let m = match self.matches(context)? {
+            Some(m) => m.clone(),
+            None => return Ok(()),
+        };
+
+        let title = self
+            .eval_value(engine, context, &m.data, |d| &d.title)?
+            .ok_or_else(|| {
+                GeneratorError::Error(format!(
+                    "Missing value for 'title' for search in page '{:?}'",
+                    output.path
+                ))
+            })?;
+        let section = self.eval_value(engine, context, &m.data, |d| &d.section)?;
+        let text = self
+            .eval_value(engine, context, &m.data, |d| &d.text)?
+            .map(|t| strip_html(&t))
+            .unwrap_or_default();
+
+        let text = match self.config.max_text_length {
+            Some(max) => text.chars().take(max).collect(),
+            None => text,
+        };
+
+        self.documents.push(Document {
+            url: output.url.clone(),
+            title: strip_html(&title),
+            section,
+            text,
+        });
+
+        Ok(())
+    }
+
+    fn outputs(&self) -> Vec<String> {
+        vec![self.config.output.clone()]
+    }
+
+    fn complete(&mut self, _: &mut dyn TemplateEngine) -> Result<()> {
+        info!(
+            "Writing search index: {:?} ({} documents)",
+            self.target,
+            self.documents.len()
+        );
+
+        let terms = if self.config.inverted_index {
+            Some(self.build_terms())
+        } else {
+            None
+        };
+
+        let index = Index {
+            documents: &self.documents,
+            terms,
+            stemming: self.config.stemming.as_deref(),
+        };
+
+        let writer = File::create(&self.target)?;
+        serde_json::to_writer(writer, &index)?;
+
+        Ok(())
+    }
+}
+
+fn algorithm_for(language: &str) -> Result<Algorithm> {
+    Ok(match language.to_lowercase().as_str() {
+        "arabic" => Algorithm::Arabic,
+        "danish" => Algorithm::Danish,
+        "dutch" => Algorithm::Dutch,
+        "english" => Algorithm::English,
+        "finnish" => Algorithm::Finnish,
+        "french" => Algorithm::French,
+        "german" => Algorithm::German,
+        "greek" => Algorithm::Greek,
+        "hungarian" => Algorithm::Hungarian,
+        "italian" => Algorithm::Italian,
+        "norwegian" => Algorithm::Norwegian,
+        "portuguese" => Algorithm::Portuguese,
+        "romanian" => Algorithm::Romanian,
+        "russian" => Algorithm::Russian,
+        "spanish" => Algorithm::Spanish,
+        "swedish" => Algorithm::Swedish,
+        "tamil" => Algorithm::Tamil,
+        "turkish" => Algorithm::Turkish,
+        _ => {
+            return Err(GeneratorError::Error(format!(
+                "Unsupported stemming language: {}",
+                language
+            ))
+            .into())
+        }
+    })
+}
+
+/// Strip all HTML tags, and collapse whitespace.
+fn strip_html(html: &str) -> String {
+    let s = RE_SCRIPT.replace_all(html, " ");
+    let s = RE_STYLE.replace_all(&s, " ");
+    let s = RE_TAG.replace_all(&s, " ");
+    let s = s
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    RE_WS.replace_all(&s, " ").trim().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_html() {
+        assert_eq!(
+            strip_html("<h1>Title</h1>\n<p>Some <em>text</em> &amp; more</p>"),
+            "Title Some text & more"
+        );
+    }
+
+    #[test]
+    fn test_strip_script() {
+        assert_eq!(
+            strip_html("<p>Foo</p><script type=\"text/javascript\">var a = 1 < 2;</script>Bar"),
+            "Foo Bar"
+        );
+    }
+
+    #[test]
+    fn test_strip_script_and_style() {
+        assert_eq!(
+            strip_html("<style>p { color: red; }</style><p>Foo</p><script>var a;</script>Bar"),
+            "Foo Bar"
+        );
+        // the end tag of a different block must not end the script
+        assert_eq!(
+            strip_html("<p>Foo</p><script>s = '</style>'; var b;</script><style>p {}</style>Bar"),
+            "Foo Bar"
+        );
+    }
+}
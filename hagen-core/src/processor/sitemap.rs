@@ -6,6 +6,7 @@ use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, Event};
 use quick_xml::Writer;
 use std::fs::File;
 use std::io::Write;
+use std::path::PathBuf;
 use url::Url;
 
 use crate::generator::{GeneratorConfig, Output};
@@ -18,7 +19,8 @@ use serde_json::Value;
 
 use log::debug;
 
-use handlebars::Handlebars;
+use crate::engine::TemplateEngine;
+use crate::minify;
 use serde::{Deserialize, Serialize};
 
 type Result<T> = std::result::Result<T, Error>;
@@ -38,26 +40,40 @@ pub struct SitemapProcessor;
 impl Processor for SitemapProcessor {
     fn create<'a, 'reg>(
         &self,
-        _: &'reg mut Handlebars,
+        _: &'reg mut dyn TemplateEngine,
         _: &Value,
         generator_config: &'a GeneratorConfig,
         processor_config: Value,
     ) -> Result<Box<dyn ProcessorContext + 'a>> {
         let config = serde_json::from_value(processor_config)?;
 
-        let writer = File::create(generator_config.output.join("sitemap.xml"))?;
+        let target = generator_config.output.join("sitemap.xml");
+        let writer = File::create(&target)?;
         let mut writer = Writer::new(writer);
 
         writer.write_event(Event::Decl(BytesDecl::new(b"1.0", Some(b"UTF-8"), None)))?;
         writer.write(b"\n")?;
+        let mut attributes = vec![("xmlns", "http://www.sitemaps.org/schemas/sitemap/0.9")];
+        if generator_config.languages.len() > 1 {
+            // for linking the translations of a page
+            attributes.push(("xmlns:xhtml", "http://www.w3.org/1999/xhtml"));
+        }
         writer.write_event(Event::Start(
-            BytesStart::borrowed_name(b"urlset").with_attributes(
-                vec![("xmlns", "http://www.sitemaps.org/schemas/sitemap/0.9")].into_iter(),
-            ),
+            BytesStart::borrowed_name(b"urlset").with_attributes(attributes.into_iter()),
         ))?;
         writer.write(b"\n")?;
 
-        Ok(Box::new(SitemapContext { config, writer }))
+        let minify = if generator_config.minify.xml {
+            Some(target)
+        } else {
+            None
+        };
+
+        Ok(Box::new(SitemapContext {
+            config,
+            writer,
+            minify,
+        }))
     }
 }
 
@@ -65,6 +81,8 @@ pub struct SitemapContext<W: Write> {
     config: SitemapProcessorConfig,
 
     writer: Writer<W>,
+    /// The file to minify, once complete.
+    minify: Option<PathBuf>,
 }
 
 #[derive(AsRefStr, AsStaticStr, EnumString)]
@@ -86,6 +104,7 @@ impl<W: Write> SitemapContext<W> {
         last_mod: Option<DateTime<Utc>>,
         change_freq: Option<ChangeFrequency>,
         priority: Option<f64>,
+        alternates: &[(String, String)],
     ) -> Result<()> {
         self.writer
             .write_event(Event::Start(BytesStart::borrowed_name(b"url")))?;
@@ -105,6 +124,20 @@ impl<W: Write> SitemapContext<W> {
             self.writer.write(b"\t")?;
             xml_write_element(&mut self.writer, "priority", format!("{:.2}", priority))?;
         }
+        for (language, href) in alternates {
+            self.writer.write(b"\t")?;
+            self.writer.write_event(Event::Empty(
+                BytesStart::borrowed_name(b"xhtml:link").with_attributes(
+                    vec![
+                        ("rel", "alternate"),
+                        ("hreflang", language.as_str()),
+                        ("href", href.as_str()),
+                    ]
+                    .into_iter(),
+                ),
+            ))?;
+            self.writer.write(b"\n")?;
+        }
 
         self.writer
             .write_event(Event::End(BytesEnd::borrowed(b"url")))?;
@@ -115,13 +148,13 @@ impl<W: Write> SitemapContext<W> {
     fn last_mod_from(
         &self,
         context: &Value,
-        handlebars: &Handlebars,
+        engine: &mut dyn TemplateEngine,
     ) -> Result<Option<DateTime<Utc>>> {
         let last_mod = self
             .config
             .last_mod
             .as_ref()
-            .map(|l| value_by_template(context, handlebars, l))
+            .map(|l| value_by_template(context, engine, l))
             .transpose()?;
 
         debug!("last_mod: {:?}", last_mod);
@@ -157,14 +190,14 @@ impl<W: Write> ProcessorContext for SitemapContext<W> {
         &mut self,
         output: &Output,
         context: &Value,
-        handlebars: &mut Handlebars,
+        engine: &mut dyn TemplateEngine,
     ) -> Result<()> {
         if !self.is_match(context)? {
             return Ok(());
         }
 
         let url = Url::from_str(&output.url)?;
-        let last_mod = self.last_mod_from(context, handlebars)?;
+        let last_mod = self.last_mod_from(context, engine)?;
 
         // change freq
 
@@ -172,7 +205,7 @@ impl<W: Write> ProcessorContext for SitemapContext<W> {
             .config
             .change_frequency
             .as_ref()
-            .map(|c| value_by_template(context, handlebars, c))
+            .map(|c| value_by_template(context, engine, c))
             .transpose()?
             .filter(|s| !s.is_empty());
 
@@ -187,7 +220,7 @@ impl<W: Write> ProcessorContext for SitemapContext<W> {
             .config
             .priority
             .as_ref()
-            .map(|p| value_by_template(context, handlebars, p))
+            .map(|p| value_by_template(context, engine, p))
             .transpose()?
             .filter(|s| !s.is_empty());
 
@@ -201,27 +234,50 @@ impl<W: Write> ProcessorContext for SitemapContext<W> {
             _ => None,
         };
 
+        // alternate languages, including the page itself
+
+        let mut alternates = Vec::new();
+        if let (Some(language), false) = (&output.language, output.translations.is_empty()) {
+            alternates.push((language.clone(), output.url.clone()));
+            for t in &output.translations {
+                alternates.push((t.language.clone(), t.url.clone()));
+            }
+        }
+
         // write entry
 
-        self.write_entry(&url, last_mod, change_freq, priority)?;
+        self.write_entry(&url, last_mod, change_freq, priority, &alternates)?;
 
         // done
 
         Ok(())
     }
 
-    fn complete(&mut self, _: &mut Handlebars) -> Result<()> {
+    fn outputs(&self) -> Vec<String> {
+        vec!["sitemap.xml".into()]
+    }
+
+    fn complete(&mut self, _: &mut dyn TemplateEngine) -> Result<()> {
         // close xml tag
         self.writer
             .write_event(Event::End(BytesEnd::borrowed(b"urlset")))?;
+        self.writer.inner().flush()?;
+
+        if let Some(ref target) = self.minify {
+            minify::minify_xml_file(target)?;
+        }
 
         Ok(())
     }
 }
 
-fn value_by_template(context: &Value, handlebars: &Handlebars, template: &str) -> Result<String> {
-    handlebars
+fn value_by_template(
+    context: &Value,
+    engine: &mut dyn TemplateEngine,
+    template: &str,
+) -> Result<String> {
+    engine
         .render_template(template, context)
-        .map_err(|err| GeneratorError::TemplateRenderError(err).into())
+        .map_err(|err| err.into())
         .map(|s| s.trim().to_string())
 }
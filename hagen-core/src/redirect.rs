@@ -0,0 +1,145 @@
+use failure::Error;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+use log::info;
+use relative_path::RelativePath;
+use url::Url;
+
+use crate::generator::Output;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// The name of the redirects file, in the root of the output directory.
+pub const REDIRECTS_FILE: &str = "_redirects";
+
+/// A redirect from an old location to a new URL.
+#[derive(Debug, Clone)]
+pub struct Redirect {
+    /// The output of the redirect page.
+    pub from: Output,
+    /// The full URL of the new location.
+    pub to: String,
+}
+
+impl Redirect {
+    /// Create a new redirect for a path, relative to the site.
+    pub fn new<S1, S2, S3>(site_url: S1, from: S2, to: S3) -> Result<Self>
+    where
+        S1: AsRef<str>,
+        S2: AsRef<str>,
+        S3: AsRef<str>,
+    {
+        let site_url = site_url.as_ref();
+        let from = Output::new(site_url, page_path(from.as_ref()), Option::None::<String>)?;
+
+        // targets may either be absolute URLs, or paths relative to the site
+
+        let to = to.as_ref();
+        let to = match Url::parse(to) {
+            Ok(url) => url.into(),
+            Err(_) => Output::new(site_url, to, Option::None::<String>)?.url,
+        };
+
+        Ok(Redirect { from, to })
+    }
+
+    /// Write the redirect page into the output directory.
+    pub fn write_page<P: AsRef<Path>>(&self, output: P) -> Result<()> {
+        let target = RelativePath::new(&self.from.path).to_path(output);
+
+        info!("Redirect '{}' -> '{}'", self.from.path, self.to);
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let url = escape_attribute(&self.to);
+
+        let mut writer = File::create(target)?;
+        write!(
+            writer,
+            r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Redirecting&hellip;</title>
+<link rel="canonical" href="{url}">
+<meta http-equiv="refresh" content="0; url={url}">
+<meta name="robots" content="noindex">
+</head>
+<body>
+<p>This page has moved to <a href="{url}">{url}</a>.</p>
+</body>
+</html>
+"#,
+            url = url
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Write a `_redirects` file, as understood by Netlify and Cloudflare Pages.
+pub fn write_redirects_file<P: AsRef<Path>>(output: P, redirects: &[Redirect]) -> Result<()> {
+    let target = output.as_ref().join(REDIRECTS_FILE);
+    info!("Writing redirects file: {:?}", target);
+
+    let mut writer = File::create(target)?;
+
+    for r in redirects {
+        let from = Url::parse(&r.from.url)?;
+        writeln!(writer, "{} {} 301", from.path(), r.to)?;
+    }
+
+    Ok(())
+}
+
+/// Turn a URL path into the path of the page which needs to be written.
+fn page_path(path: &str) -> String {
+    let last = path.rsplit('/').next().unwrap_or_default();
+    if last.is_empty() {
+        format!("{}index.html", path)
+    } else if !last.contains('.') {
+        format!("{}/index.html", path)
+    } else {
+        path.into()
+    }
+}
+
+fn escape_attribute(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_path() {
+        assert_eq!(page_path("/foo/"), "/foo/index.html");
+        assert_eq!(page_path("/foo/bar"), "/foo/bar/index.html");
+        assert_eq!(page_path("/foo/bar.html"), "/foo/bar.html");
+    }
+
+    #[test]
+    fn test_redirect_path() -> Result<()> {
+        let r = Redirect::new("http://localhost/base", "/old/", "/new/page.html")?;
+        assert_eq!(r.from.path, "old/index.html");
+        assert_eq!(r.from.url, "http://localhost/base/old/");
+        assert_eq!(r.to, "http://localhost/base/new/page.html");
+        Ok(())
+    }
+
+    #[test]
+    fn test_redirect_absolute() -> Result<()> {
+        let r = Redirect::new("http://localhost/base", "/old.html", "https://foo.bar/baz")?;
+        assert_eq!(r.from.path, "old.html");
+        assert_eq!(r.to, "https://foo.bar/baz");
+        Ok(())
+    }
+}
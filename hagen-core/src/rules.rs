@@ -4,11 +4,13 @@ use std::fs::File;
 use serde_yaml;
 use std::io;
 
-use jsonpath_lib::Selector;
+use jsonpath_lib::{Compiled, Selector};
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
+use std::collections::BTreeMap;
 use std::path::Path;
 
+use crate::engine::Engine;
 use crate::error::GeneratorError;
 
 type Result<T> = std::result::Result<T, Error>;
@@ -16,6 +18,8 @@ type Result<T> = std::result::Result<T, Error>;
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Rule {
+    /// An optional name, allowing a site to override a rule of the theme.
+    pub name: Option<String>,
     pub selector_type: String,
     pub selector: Option<String>,
     pub template: Option<String>,
@@ -31,15 +35,239 @@ pub trait RuleProcessor {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Render {
     pub site: Site,
+    /// The template engine to use.
+    #[serde(default)]
+    pub engine: Engine,
+    /// The theme directory, relative to the root.
+    pub theme: Option<String>,
+    /// The output directory, relative to the root. Defaults to `output`.
+    pub output: Option<String>,
+    #[serde(default)]
+    pub directories: Directories,
+    /// The languages of a multilingual site, the first one is the default language.
+    ///
+    /// Content which isn't translated into a language falls back to the default language.
+    /// The RSS feed only covers a single language, the default one.
+    #[serde(default)]
+    pub languages: Vec<Language>,
+    /// How to handle content files with the same name in the same directory.
+    #[serde(default)]
+    pub collisions: Collisions,
+    /// How to handle multiple pages or assets being written to the same output file.
+    #[serde(default)]
+    pub conflicts: Conflicts,
+    /// Options of the image pipeline.
+    #[serde(default)]
+    pub images: ImageOptions,
+    /// Files to ignore in the content, data and asset directories.
+    #[serde(default)]
+    pub ignore: IgnoreConfig,
+    /// Minification of rendered pages and processor outputs.
+    #[serde(default)]
+    pub minify: MinifyOptions,
+    /// Write pre-compressed (gzip, Brotli) variants of the output files.
+    pub compress: Option<CompressOptions>,
+    /// Default front matter values for content matching a path glob.
+    #[serde(default)]
+    pub cascade: Vec<Cascade>,
+    /// JSON schemas for validating the front matter of the content.
+    #[serde(default)]
+    pub schemas: Schemas,
+    #[serde(default)]
     pub rules: Vec<Rule>,
+    #[serde(default)]
     pub assets: Vec<Asset>,
     #[serde(default)]
     pub processors: Map<String, Value>,
+    /// The build timestamp (RFC 3339), for relative timestamps. Defaults to the current time.
+    pub build_time: Option<String>,
+    /// Redirects from old paths to new paths or URLs.
+    #[serde(default)]
+    pub redirects: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct Site {
     pub basename: String,
+    /// Write a `_redirects` file, in addition to the redirect pages.
+    #[serde(default)]
+    pub redirects_file: bool,
+    /// The language of a single-language site, selects the catalog of the `t` helper.
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+/// The source directories of the site, relative to the root.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Directories {
+    pub content: String,
+    pub data: String,
+    /// Template directories, later directories override templates of earlier ones.
+    pub templates: Vec<String>,
+    /// The translation files of the `t` helper, one per language: `i18n/de.yaml`.
+    pub i18n: String,
+}
+
+impl Default for Directories {
+    fn default() -> Self {
+        Directories {
+            content: "content".into(),
+            data: "data".into(),
+            templates: vec!["templates".into()],
+            i18n: "i18n".into(),
+        }
+    }
+}
+
+/// A language of a multilingual site.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Language {
+    /// The language code, used as file suffix (`index.de.md`) and URL prefix (`/de/`).
+    pub code: String,
+    /// The display name, e.g. `Deutsch`.
+    pub name: Option<String>,
+    /// A separate content directory of this language, relative to the root.
+    ///
+    /// Content files without a language suffix in this directory belong to this language.
+    pub content: Option<String>,
+}
+
+/// How to handle content files with the same name, like `index.md` and `index.html`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum Collisions {
+    /// Report a warning, the last file (by file name) wins. Matches the behavior of earlier
+    /// versions, which silently kept one of the files.
+    #[default]
+    Warn,
+    /// Fail loading the content.
+    Fail,
+    /// Keep all entries, using the file name (including the extension) as key.
+    Disambiguate,
+}
+
+/// How to handle output files, which are produced more than once.
+///
+/// Assets may always override earlier assets, e.g. the assets of a theme.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum Conflicts {
+    /// Report a warning, the last file wins. Matches the behavior of earlier versions, which
+    /// silently overwrote the file.
+    #[default]
+    Warn,
+    /// Fail the build.
+    Fail,
+}
+
+/// Options of the image pipeline, used by the `image` helper.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct ImageOptions {
+    /// The widths to create, images are never scaled up.
+    pub widths: Vec<u32>,
+    /// Additional formats to create, none by default.
+    pub formats: Vec<ImageFormat>,
+    /// The quality (1-100), for lossy formats.
+    pub quality: u8,
+    /// The cache directory of encoded images, relative to the root.
+    pub cache: String,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        ImageOptions {
+            widths: vec![480, 960, 1920],
+            formats: Vec::new(),
+            quality: 80,
+            cache: ".hagen-cache/images".into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    /// WebP, lossless. Usually larger than a lossy JPEG, but smaller than a PNG.
+    Webp,
+    /// AVIF, requires the `avif` feature.
+    Avif,
+}
+
+/// Minification of generated files, disabled by default.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+#[serde(default)]
+pub struct MinifyOptions {
+    /// Minify HTML pages rendered by rules.
+    pub html: bool,
+    /// Minify XML files written by processors, like the sitemap and RSS feed.
+    pub xml: bool,
+}
+
+/// Options for writing pre-compressed variants of the output files.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct CompressOptions {
+    /// Write `.gz` files.
+    pub gzip: bool,
+    /// Write `.br` files.
+    pub brotli: bool,
+    /// The minimum size (in bytes) of a file to compress.
+    pub threshold: u64,
+    /// The extensions of the files to compress.
+    pub extensions: Vec<String>,
+    /// The maximum ratio of compressed to original size, files compressing worse are skipped.
+    pub max_ratio: f64,
+}
+
+impl Default for CompressOptions {
+    fn default() -> Self {
+        CompressOptions {
+            gzip: true,
+            brotli: true,
+            threshold: 1024,
+            extensions: ["html", "css", "js", "xml", "json", "svg"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            max_ratio: 0.9,
+        }
+    }
+}
+
+/// Patterns of files to ignore, when loading content and copying assets.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct IgnoreConfig {
+    /// Gitignore style patterns, relative to the directory being processed.
+    pub patterns: Vec<String>,
+    /// Process hidden files (starting with a `.`) of the content and assets, which are skipped
+    /// by default. See `Asset::hidden` for copying only some of them.
+    pub include_hidden: bool,
+}
+
+/// Default front matter values, for all content matching the path glob.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Cascade {
+    /// The path glob, relative to the content directory, e.g. `documentation/**`.
+    pub path: String,
+    pub values: Map<String, Value>,
+}
+
+/// JSON schemas (as YAML files), relative to the root.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Schemas {
+    /// Schemas by the layout of the front matter. These take precedence over types.
+    pub layouts: BTreeMap<String, String>,
+    /// Schemas by the content type, like `md` or `html`.
+    pub types: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -47,6 +275,46 @@ pub struct Asset {
     pub dir: String,
     pub to: Option<String>,
     pub glob: Option<String>,
+    /// Compile the Sass/SCSS files of the directory, instead of copying the files.
+    pub sass: Option<SassOptions>,
+    /// Minify CSS and JavaScript files.
+    #[serde(default)]
+    pub minify: bool,
+    /// Files to concatenate into a single file.
+    #[serde(default)]
+    pub bundles: Vec<Bundle>,
+    /// Add a hash of the content to the file names, and record them in the asset manifest.
+    #[serde(default)]
+    pub fingerprint: bool,
+    /// Hidden files and directories to copy, by name: `[.well-known, .htaccess]`. Other hidden
+    /// files, like `.DS_Store`, are skipped.
+    #[serde(default)]
+    pub hidden: Vec<String>,
+}
+
+/// A list of CSS or JavaScript files, concatenated into a single file.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Bundle {
+    /// The output file, relative to the target directory of the asset.
+    pub output: String,
+    /// The files to concatenate, relative to the directory of the asset.
+    pub files: Vec<String>,
+}
+
+/// Options for compiling Sass/SCSS assets.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct SassOptions {
+    /// Additional paths to resolve imports, relative to the root.
+    pub include_paths: Vec<String>,
+    /// Produce compressed CSS output.
+    pub minify: bool,
+    /// Write a source map next to each compiled file: `custom.css.map`.
+    ///
+    /// The `minify` option of the asset is not applied to the compiled files then, use the
+    /// `minify` option of Sass instead.
+    pub source_map: bool,
 }
 
 impl Render {
@@ -61,6 +329,47 @@ impl Render {
 }
 
 impl Rule {
+    /// A short description of the rule, used when reporting problems.
+    pub fn describe(&self) -> String {
+        match self.name {
+            Some(ref name) => name.clone(),
+            None => format!(
+                "{}:{} -> {}",
+                self.selector_type,
+                self.selector.as_deref().unwrap_or("*"),
+                self.output_pattern
+            ),
+        }
+    }
+
+    /// Validate the rule, without evaluating it against the content.
+    pub fn validate(&self) -> Result<()> {
+        self.processor()?;
+
+        if self.selector_type == "jsonpath" {
+            if let Some(ref selector) = self.selector {
+                check_path(selector)?;
+            }
+        }
+
+        for (k, v) in &self.context {
+            match v {
+                Value::String(path) => check_path(path).map_err(|err| {
+                    GeneratorError::Error(format!("Invalid context mapping '{}': {}", k, err))
+                })?,
+                _ => {
+                    return Err(GeneratorError::Error(format!(
+                        "Context value of '{}' must be a string/JSON path",
+                        k
+                    ))
+                    .into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn processor(&self) -> Result<Box<dyn RuleProcessor>> {
         match self.selector_type.as_str() {
             "layout" => Ok(Box::new(LayoutProcessor {
@@ -84,6 +393,12 @@ impl Rule {
     }
 }
 
+/// Check if a JSON path can be parsed.
+fn check_path(path: &str) -> Result<()> {
+    Compiled::compile(path).map_err(GeneratorError::JsonPath)?;
+    Ok(())
+}
+
 pub struct JsonPathProcessor {
     path: String,
 }
@@ -194,3 +509,52 @@ impl RuleProcessor for LayoutProcessor {
         Ok(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(selector_type: &str, selector: Option<&str>) -> Rule {
+        Rule {
+            name: None,
+            selector_type: selector_type.into(),
+            selector: selector.map(|s| s.into()),
+            template: None,
+            output_pattern: "index.html".into(),
+            context: Map::new(),
+        }
+    }
+
+    #[test]
+    fn test_directories() {
+        let directories: Directories = serde_yaml::from_str("data: db").unwrap();
+
+        assert_eq!(directories.content, "content");
+        assert_eq!(directories.data, "db");
+        assert_eq!(directories.templates, vec!["templates"]);
+        assert_eq!(directories.i18n, "i18n");
+    }
+
+    #[test]
+    fn test_validate_ok() {
+        let mut r = rule("jsonpath", Some("$..[?(@.type=='post')]"));
+        r.context
+            .insert("post".into(), Value::String("$.frontMatter".into()));
+        assert!(r.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_unknown_type() {
+        assert!(rule("foo", None).validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_invalid_path() {
+        assert!(rule("jsonpath", Some("$.[")).validate().is_err());
+        assert!(rule("jsonpath", None).validate().is_err());
+
+        let mut r = rule("layout", None);
+        r.context.insert("post".into(), Value::Bool(true));
+        assert!(r.validate().is_err());
+    }
+}
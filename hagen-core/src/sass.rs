@@ -0,0 +1,527 @@
+use failure::Error;
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use grass::OutputStyle;
+use log::{debug, info};
+use serde_json::json;
+
+use crate::copy::walk_dir;
+use crate::error::GeneratorError;
+use crate::ignores::Ignores;
+use crate::rules::SassOptions;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Compile all Sass/SCSS entry points of a directory into CSS files.
+///
+/// Partials (files starting with an `_`) are not compiled on their own. Include paths are
+/// relative to the root of the site. Returns the written files, relative to the target,
+/// including source maps.
+pub fn compile_dir<P1, P2, P3, S>(
+    root: P1,
+    from: P2,
+    to: P3,
+    glob: Option<S>,
+    ignores: &Ignores,
+    options: &SassOptions,
+) -> Result<Vec<PathBuf>>
+where
+    P1: AsRef<Path>,
+    P2: AsRef<Path>,
+    P3: AsRef<Path>,
+    S: AsRef<str>,
+{
+    let to = to.as_ref();
+
+    let include_paths: Vec<PathBuf> = options
+        .include_paths
+        .iter()
+        .map(|p| root.as_ref().join(p))
+        .collect();
+
+    let style = if options.minify {
+        OutputStyle::Compressed
+    } else {
+        OutputStyle::Expanded
+    };
+    let mut compiled = Vec::new();
+
+    walk_dir(from, glob, ignores, |source, relative| {
+        if !source.is_file() || !is_entry_point(source) {
+            return Ok(());
+        }
+
+        let relative = relative.with_extension("css");
+        let target = to.join(&relative);
+
+        info!("Compiling Sass: {:?} -> {:?}", source, target);
+
+        let recording = RecordingFs::default();
+        let grass_options = grass::Options::default()
+            .style(style)
+            .load_paths(&include_paths)
+            .fs(&recording);
+
+        let mut css = grass::from_path(source, &grass_options).map_err(|err| {
+            GeneratorError::Error(format!("Failed to compile {:?}: {}", source, err))
+        })?;
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        if options.source_map {
+            let map = relative.with_extension("css.map");
+            let map_name = file_name(&map);
+
+            let sources = recording.sources.into_inner();
+            fs::write(to.join(&map), source_map(&target, &css, &sources)?)?;
+            debug!("Wrote: {:?}", to.join(&map));
+
+            css.push_str(&format!("/*# sourceMappingURL={} */\n", map_name));
+            compiled.push(map);
+        }
+
+        fs::write(&target, css)?;
+        debug!("Wrote: {:?}", target);
+
+        compiled.push(relative);
+
+        Ok(())
+    })?;
+
+    Ok(compiled)
+}
+
+/// Check if the file is a Sass entry point, and not a partial.
+fn is_entry_point(path: &Path) -> bool {
+    let sass = matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("scss") | Some("sass")
+    );
+    let partial = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .map(|n| n.starts_with('_'))
+        .unwrap_or(false);
+
+    sass && !partial
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// A file read by the compiler.
+#[derive(Debug)]
+struct Source {
+    path: PathBuf,
+    content: String,
+}
+
+/// The file system of the compiler, recording the files it reads.
+#[derive(Debug, Default)]
+struct RecordingFs {
+    sources: RefCell<Vec<Source>>,
+}
+
+impl grass::Fs for RecordingFs {
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let data = fs::read(path)?;
+        self.sources.borrow_mut().push(Source {
+            path: path.into(),
+            content: String::from_utf8_lossy(&data).into_owned(),
+        });
+        Ok(data)
+    }
+}
+
+/// Create a source map (version 3) for a compiled file, including the content of the sources.
+///
+/// The compiler doesn't track positions. So every rule and declaration of the CSS is mapped
+/// to the first matching selector or property in the sources, following the previous match.
+/// For nested rules and mixins this is an approximation.
+fn source_map(target: &Path, css: &str, sources: &[Source]) -> Result<String> {
+    let dir = target.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut mappings = String::new();
+    let mut line = 0;
+    // the previous values, fields of the segments are relative to them
+    let mut previous = (0, 0, 0, 0);
+    let mut cursor = (0, 0);
+
+    for item in css_items(css) {
+        let (index, offset, len) = match find_item(&item, sources, cursor) {
+            Some(found) => found,
+            None => continue,
+        };
+        cursor = (index, offset + len);
+
+        let content = &sources[index].content[..offset];
+        let source_line = content.matches('\n').count() as i64;
+        let source_column = content.rsplit('\n').next().unwrap_or("").chars().count() as i64;
+
+        if item.line > line {
+            mappings.push_str(&";".repeat(item.line - line));
+            line = item.line;
+            previous.0 = 0;
+        } else if !mappings.is_empty() && !mappings.ends_with(';') {
+            mappings.push(',');
+        }
+
+        let segment = (item.column as i64, index as i64, source_line, source_column);
+        vlq(&mut mappings, segment.0 - previous.0);
+        vlq(&mut mappings, segment.1 - previous.1);
+        vlq(&mut mappings, segment.2 - previous.2);
+        vlq(&mut mappings, segment.3 - previous.3);
+        previous = segment;
+    }
+
+    let map = json!({
+        "version": 3,
+        "file": file_name(target),
+        "sources": sources
+            .iter()
+            .map(|s| relative_path(dir, &s.path))
+            .collect::<Vec<_>>(),
+        "sourcesContent": sources.iter().map(|s| &s.content).collect::<Vec<_>>(),
+        "names": [],
+        "mappings": mappings,
+    });
+
+    Ok(serde_json::to_string(&map)?)
+}
+
+/// A rule (selector or at-rule) or a declaration of the compiled CSS.
+#[derive(Debug, PartialEq)]
+struct Item {
+    /// The zero-based line of the start.
+    line: usize,
+    /// The zero-based column of the start.
+    column: usize,
+    text: String,
+    /// The item starts a block, so it's a rule and not a declaration.
+    block: bool,
+}
+
+/// Split CSS into its rules and declarations, skipping comments and strings.
+fn css_items(css: &str) -> Vec<Item> {
+    let mut items = Vec::new();
+
+    let (mut line, mut column) = (0, 0);
+    let mut start: Option<(usize, usize, usize)> = None;
+    let mut quote: Option<char> = None;
+    let mut comment = false;
+    let mut previous = '\0';
+
+    let mut chars = css.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if comment {
+            comment = !(previous == '*' && c == '/');
+        } else if let Some(q) = quote {
+            if c == q && previous != '\\' {
+                quote = None;
+            }
+        } else if c == '/' && chars.peek().map(|(_, n)| *n) == Some('*') {
+            comment = true;
+            // skip the `*`, so that it doesn't close the comment
+            chars.next();
+            column += 1;
+        } else if c == '{' || c == ';' || c == '}' {
+            if let Some((line, column, offset)) = start.take() {
+                let text = css[offset..i].trim();
+                if !text.is_empty() {
+                    items.push(Item {
+                        line,
+                        column,
+                        text: text.into(),
+                        block: c == '{',
+                    });
+                }
+            }
+        } else {
+            if start.is_none() && !c.is_whitespace() {
+                start = Some((line, column, i));
+            }
+            if c == '"' || c == '\'' {
+                quote = Some(c);
+            }
+        }
+
+        if c == '\n' {
+            line += 1;
+            column = 0;
+        } else {
+            column += 1;
+        }
+        previous = c;
+    }
+
+    items
+}
+
+/// Find an item in the sources, first after the cursor, then anywhere.
+///
+/// Returns the index of the source, the offset and the length of the match.
+fn find_item(
+    item: &Item,
+    sources: &[Source],
+    cursor: (usize, usize),
+) -> Option<(usize, usize, usize)> {
+    let candidates: Vec<&str> = if item.block {
+        let selector = item.text.split(',').next().unwrap_or("").trim();
+        let compound = selector
+            .rsplit(|c: char| c.is_whitespace() || c == '>' || c == '+' || c == '~')
+            .next()
+            .unwrap_or("");
+        let simple = compound
+            .rfind(['.', '#', ':', '['])
+            .map(|i| &compound[i..])
+            .unwrap_or(compound);
+        vec![selector, compound, simple]
+    } else {
+        vec![item.text.split(':').next().unwrap_or("").trim()]
+    };
+
+    for key in candidates.into_iter().filter(|k| !k.is_empty()) {
+        let (index, offset) = cursor;
+        if let Some(source) = sources.get(index) {
+            if let Some(found) = find_key(&source.content, key, offset, !item.block) {
+                return Some((index, found, key.len()));
+            }
+        }
+        let found = sources
+            .iter()
+            .enumerate()
+            .find_map(|(i, s)| find_key(&s.content, key, 0, !item.block).map(|f| (i, f)));
+        if let Some((index, found)) = found {
+            return Some((index, found, key.len()));
+        }
+    }
+
+    None
+}
+
+/// Find a selector or property (followed by a `:`) as a whole word.
+fn find_key(content: &str, key: &str, from: usize, property: bool) -> Option<usize> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '-' || c == '_';
+
+    let mut from = from.min(content.len());
+    while let Some(found) = content.get(from..)?.find(key) {
+        let offset = from + found;
+        let end = offset + key.len();
+        from = end;
+
+        let before = content[..offset].chars().next_back();
+        let after = content[end..].chars().next();
+        if before.is_some_and(is_word) && key.starts_with(is_word) {
+            continue;
+        }
+        if after.is_some_and(is_word) && key.ends_with(is_word) {
+            continue;
+        }
+        if property && !content[end..].trim_start().starts_with(':') {
+            continue;
+        }
+        return Some(offset);
+    }
+
+    None
+}
+
+/// Append a value as base64 VLQ.
+fn vlq(out: &mut String, value: i64) {
+    const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut value = if value < 0 {
+        ((-value) << 1) | 1
+    } else {
+        value << 1
+    };
+    loop {
+        let mut digit = value & 0b11111;
+        value >>= 5;
+        if value > 0 {
+            digit |= 0b100000;
+        }
+        out.push(BASE64[digit as usize] as char);
+        if value == 0 {
+            break;
+        }
+    }
+}
+
+/// The path of a file relative to a directory, with `/` as separator.
+fn relative_path(dir: &Path, path: &Path) -> String {
+    let dir: Vec<Component> = dir
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .collect();
+    let path: Vec<Component> = path
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .collect();
+
+    let common = dir.iter().zip(&path).take_while(|(a, b)| a == b).count();
+
+    std::iter::repeat_n("..".to_string(), dir.len() - common)
+        .chain(
+            path[common..]
+                .iter()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned()),
+        )
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_point() {
+        assert!(is_entry_point(Path::new("scss/custom.scss")));
+        assert!(is_entry_point(Path::new("scss/custom.sass")));
+        assert!(!is_entry_point(Path::new("scss/_variables.scss")));
+        assert!(!is_entry_point(Path::new("scss/custom.css")));
+    }
+
+    #[test]
+    fn test_vlq() {
+        let mut s = String::new();
+        for value in &[0, 1, -1, 15, 16, -17, 1000] {
+            vlq(&mut s, *value);
+        }
+        assert_eq!(s, "ACDegBjBw+B");
+    }
+
+    #[test]
+    fn test_css_items() {
+        let items = css_items("/* a; b */\n.a{color:red;content:\"x;y\"}\n");
+        let items: Vec<_> = items
+            .iter()
+            .map(|i| (i.line, i.column, i.text.as_str(), i.block))
+            .collect();
+        assert_eq!(
+            items,
+            vec![
+                (1, 0, ".a", true),
+                (1, 3, "color:red", false),
+                (1, 13, "content:\"x;y\"", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_relative_path() {
+        assert_eq!(
+            relative_path(
+                Path::new("./output/css"),
+                Path::new("./assets/scss/main.scss")
+            ),
+            "../../assets/scss/main.scss"
+        );
+        assert_eq!(
+            relative_path(
+                Path::new("/site/output"),
+                Path::new("/site/output/main.scss")
+            ),
+            "main.scss"
+        );
+    }
+
+    /// Decode the mappings into absolute (line, column, source, source line, source column).
+    fn decode(mappings: &str) -> Vec<(i64, i64, i64, i64, i64)> {
+        const BASE64: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+        let mut result = Vec::new();
+        let mut previous = [0; 4];
+        for (line, segments) in mappings.split(';').enumerate() {
+            previous[0] = 0;
+            for segment in segments.split(',').filter(|s| !s.is_empty()) {
+                let (mut values, mut value, mut shift) = (Vec::new(), 0, 0);
+                for c in segment.chars() {
+                    let digit = BASE64.find(c).unwrap() as i64;
+                    value += (digit & 31) << shift;
+                    shift += 5;
+                    if digit & 32 == 0 {
+                        let sign = if value & 1 == 1 { -1 } else { 1 };
+                        values.push(sign * (value >> 1));
+                        value = 0;
+                        shift = 0;
+                    }
+                }
+                for (p, v) in previous.iter_mut().zip(values) {
+                    *p += v;
+                }
+                let [column, source, source_line, source_column] = previous;
+                result.push((line as i64, column, source, source_line, source_column));
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn test_source_map() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("hagen-sass-{}", std::process::id()));
+        let from = dir.join("scss");
+        let to = dir.join("output");
+        fs::create_dir_all(&from)?;
+        fs::write(
+            from.join("main.scss"),
+            "@import \"vars\";\n.a {\n  color: $c;\n  .b {\n    margin: 0;\n  }\n}\n",
+        )?;
+        fs::write(from.join("_vars.scss"), "$c: red;\n")?;
+
+        let options = SassOptions {
+            source_map: true,
+            ..Default::default()
+        };
+        let ignores = Ignores::new(&from, &Default::default())?;
+        let mut compiled = compile_dir(&dir, &from, &to, None::<&str>, &ignores, &options)?;
+        compiled.sort();
+
+        let css = fs::read_to_string(to.join("main.css"))?;
+        let map: serde_json::Value = serde_json::from_slice(&fs::read(to.join("main.css.map"))?)?;
+        fs::remove_dir_all(&dir)?;
+
+        assert_eq!(
+            compiled,
+            vec![PathBuf::from("main.css"), PathBuf::from("main.css.map")]
+        );
+        assert_eq!(
+            css,
+            ".a {\n  color: red;\n}\n.a .b {\n  margin: 0;\n}\n/*# sourceMappingURL=main.css.map */\n"
+        );
+        assert_eq!(map["file"], "main.css");
+        assert_eq!(
+            map["sources"],
+            json!(["../scss/main.scss", "../scss/_vars.scss"])
+        );
+        assert_eq!(map["sourcesContent"][1], "$c: red;\n");
+        assert_eq!(
+            decode(map["mappings"].as_str().unwrap()),
+            vec![
+                (0, 0, 0, 1, 0),
+                (1, 2, 0, 2, 2),
+                (3, 0, 0, 3, 2),
+                (4, 2, 0, 4, 4)
+            ]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+use failure::Error;
+use std::fs::File;
+use std::path::Path;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::rules::{Asset, Render, Rule};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// The configuration fragment of a theme, read from the `hagen.yaml` file of the theme.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Theme {
+    pub rules: Vec<Rule>,
+    pub assets: Vec<Asset>,
+    pub processors: Map<String, Value>,
+}
+
+impl Theme {
+    /// Load the theme configuration from the theme directory.
+    ///
+    /// A theme doesn't need to provide a configuration file.
+    pub fn load_from<P: AsRef<Path>>(path: P) -> Result<Theme> {
+        let path = path.as_ref().join("hagen.yaml");
+        if !path.is_file() {
+            return Ok(Default::default());
+        }
+
+        info!("Loading theme configuration: {:?}", path);
+        Ok(serde_yaml::from_reader(File::open(path)?)?)
+    }
+
+    /// Apply the theme to the site configuration.
+    ///
+    /// Values of the site take precedence over the ones of the theme. The `theme` is the path
+    /// of the theme directory, as configured in the site configuration.
+    pub fn apply(self, theme: &str, render: &mut Render) {
+        let theme = Path::new(theme);
+
+        // templates of the site override the ones of the theme
+
+        render
+            .directories
+            .templates
+            .insert(0, theme.join("templates").to_string_lossy().into_owned());
+
+        // rules of the site override the theme rules with the same name
+
+        let mut rules: Vec<Rule> = self
+            .rules
+            .into_iter()
+            .filter(|r| match r.name {
+                Some(ref name) => !render.rules.iter().any(|sr| sr.name.as_ref() == Some(name)),
+                None => true,
+            })
+            .collect();
+        rules.append(&mut render.rules);
+        render.rules = rules;
+
+        // theme assets are copied first, so that site assets can override them
+
+        let mut assets: Vec<Asset> = self
+            .assets
+            .into_iter()
+            .map(|mut a| {
+                a.dir = theme.join(&a.dir).to_string_lossy().into_owned();
+                a
+            })
+            .collect();
+        assets.append(&mut render.assets);
+        render.assets = assets;
+
+        // processor configurations of the site override the ones of the theme
+
+        for (k, v) in self.processors {
+            render.processors.entry(k).or_insert(v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THEME: &str = r#"
+rules:
+  - name: page
+    selectorType: layout
+    template: theme-page
+    outputPattern: "{{ metadata.name }}.html"
+  - selectorType: jsonpath
+    selector: $.feed
+    template: feed
+    outputPattern: feed.xml
+assets:
+  - dir: assets
+    to: theme
+processors:
+  sitemap: {}
+  search: {output: theme-search.json}
+"#;
+
+    const SITE: &str = r#"
+site:
+  basename: https://example.com/
+directories:
+  templates: [templates, layouts]
+rules:
+  - name: page
+    selectorType: layout
+    template: page
+    outputPattern: "{{ metadata.name }}.html"
+assets:
+  - dir: static
+processors:
+  search: {output: search.json}
+"#;
+
+    fn apply() -> Render {
+        let theme: Theme = serde_yaml::from_str(THEME).unwrap();
+        let mut render = Render::load(SITE.as_bytes()).unwrap();
+        theme.apply("themes/basic", &mut render);
+        render
+    }
+
+    #[test]
+    fn test_rules() {
+        let render = apply();
+        let templates: Vec<_> = render.rules.iter().map(|r| r.template.as_deref()).collect();
+
+        // the theme rule named `page` is replaced by the one of the site
+        assert_eq!(templates, vec![Some("feed"), Some("page")]);
+    }
+
+    #[test]
+    fn test_assets() {
+        let render = apply();
+        let dirs: Vec<_> = render.assets.iter().map(|a| a.dir.as_str()).collect();
+
+        // theme assets are copied first, so that the ones of the site win
+        assert_eq!(dirs, vec!["themes/basic/assets", "static"]);
+        assert_eq!(render.assets[0].to.as_deref(), Some("theme"));
+    }
+
+    #[test]
+    fn test_templates() {
+        let render = apply();
+
+        // later directories override templates of earlier ones
+        assert_eq!(
+            render.directories.templates,
+            vec!["themes/basic/templates", "templates", "layouts"]
+        );
+    }
+
+    #[test]
+    fn test_processors() {
+        let render = apply();
+
+        assert_eq!(render.processors["sitemap"], serde_json::json!({}));
+        assert_eq!(
+            render.processors["search"],
+            serde_json::json!({"output": "search.json"})
+        );
+    }
+
+    #[test]
+    fn test_empty_theme() {
+        let mut render = Render::load(SITE.as_bytes()).unwrap();
+        Theme::default().apply("themes/basic", &mut render);
+
+        assert_eq!(render.rules.len(), 1);
+        assert_eq!(render.assets.len(), 1);
+        assert_eq!(render.processors.len(), 1);
+        assert_eq!(render.directories.templates[0], "themes/basic/templates");
+    }
+}
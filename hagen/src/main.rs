@@ -1,8 +1,9 @@
 use env_logger::Env;
-use failure::{Error, Fail};
+use failure::Error;
 
 use structopt::StructOpt;
 
+use hagen_core::diagnostic::Diagnostic;
 use hagen_core::generator::GeneratorBuilder;
 
 use std::env;
@@ -21,32 +22,91 @@ pub struct Options {
     #[structopt(short = "r", long = "root")]
     root: Option<String>,
 
+    /// The output directory. Overrides the "output" value of "hagen.yaml".
+    #[structopt(short = "o", long = "output")]
+    output: Option<String>,
+
+    /// Don't clean the whole output directory, only remove files of the previous build.
+    #[structopt(long = "no-clean")]
+    no_clean: bool,
+
+    /// Continue the build when rendering fails, and report all errors at the end.
+    #[structopt(short = "k", long = "keep-going")]
+    keep_going: bool,
+
     /// Dump the content files as well.
     #[structopt(short = "D", long = "dump")]
     dump: bool,
+
+    /// The format of error messages.
+    #[structopt(
+        long = "message-format",
+        default_value = "human",
+        possible_values = &["human", "json"]
+    )]
+    message_format: String,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
 }
 
-fn hag_run() -> Result<()> {
-    let opts = Options::from_args();
+#[derive(Clone, StructOpt)]
+pub enum Command {
+    /// Build the site. This is the default.
+    Build,
+    /// Validate configuration, content and templates, without writing any output.
+    Check,
+}
 
+fn hag_run(opts: Options) -> Result<()> {
     let root = match opts.root {
         Some(x) => PathBuf::from(x),
         None => env::current_dir().expect("Failed to get current directory"),
     };
 
+    let output = opts
+        .output
+        .map(|o| env::current_dir().map(|d| d.join(o)))
+        .transpose()?;
+
     let mut generator = GeneratorBuilder::new(&root)
         .dump(opts.dump)
         .override_basename(opts.basename)
+        .override_output(output)
+        .clean(!opts.no_clean)
+        .keep_going(opts.keep_going)
         .build();
 
-    Ok(generator.run()?)
+    let result = match opts.command {
+        Some(Command::Check) => generator.check(),
+        Some(Command::Build) | None => generator.run(),
+    };
+
+    // report errors collected in "keep going" mode
+
+    for err in generator.errors() {
+        report(err, &opts.message_format);
+    }
+
+    Ok(result?)
 }
 
-/// Exit on error, showing cause of error
-fn hag_exit(err: failure::Error) -> ! {
-    for cause in Fail::iter_chain(err.as_fail()) {
-        eprintln!("{}: {}", cause.name().unwrap_or("Error"), cause);
+/// Report an error, in the requested message format
+fn report(err: &failure::Error, message_format: &str) {
+    let diagnostic = Diagnostic::from_error(err);
+
+    match message_format {
+        "json" => match serde_json::to_string(&diagnostic) {
+            Ok(json) => println!("{}", json),
+            Err(_) => eprintln!("{}", diagnostic.report()),
+        },
+        _ => eprintln!("{}", diagnostic.report()),
     }
+}
+
+/// Exit on error, showing cause of error
+fn hag_exit(err: failure::Error, message_format: &str) -> ! {
+    report(&err, message_format);
 
     std::process::exit(1)
 }
@@ -54,8 +114,11 @@ fn hag_exit(err: failure::Error) -> ! {
 fn main() {
     env_logger::from_env(Env::default().default_filter_or("info")).init();
 
-    match hag_run() {
-        Err(e) => hag_exit(e),
+    let opts = Options::from_args();
+    let message_format = opts.message_format.clone();
+
+    match hag_run(opts) {
+        Err(e) => hag_exit(e, &message_format),
         Ok(()) => {}
     }
 }